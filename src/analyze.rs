@@ -0,0 +1,311 @@
+use crate::png::{ChunkRef, ChunkType, PngRef};
+use std::fmt::{self, Display, Formatter};
+
+/// Text chunks longer than this are unusual enough to point out.
+const MAX_TEXT_LENGTH: usize = 4096;
+
+/// Ancillary chunks shorter than this are too small for their entropy to mean much.
+const MIN_ENTROPY_LENGTH: usize = 128;
+
+/// Fraction of the highest entropy possible for a chunk's length above which its data looks
+/// random, as encrypted or compressed data does.
+const ENTROPY_RATIO: f64 = 0.9;
+
+/// Ancillary chunks whose data is compressed, and therefore expected to look random.
+const COMPRESSED_CHUNKS: [&[u8; 4]; 4] = [b"zTXt", b"iTXt", b"iCCP", b"fdAT"];
+
+/// Something about the structure of a PNG file that could be hiding data.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Finding {
+    /// A private chunk, which no standard decoder knows what to do with.
+    PrivateChunk {
+        offset: usize,
+        chunk_type: ChunkType,
+        length: u32,
+    },
+    /// Bytes after the IEND chunk.
+    TrailingData { offset: usize, length: usize },
+    /// A text chunk much larger than the usual title or comment.
+    OversizedText {
+        offset: usize,
+        chunk_type: ChunkType,
+        length: u32,
+    },
+    /// IDAT chunks that aren't back to back, or sizes that no encoder would pick.
+    UnusualIdatSplit {
+        reason: &'static str,
+        sizes: Vec<u32>,
+    },
+    /// An ancillary chunk whose data looks random.
+    HighEntropy {
+        offset: usize,
+        chunk_type: ChunkType,
+        entropy: f64,
+    },
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::PrivateChunk {
+                offset,
+                chunk_type,
+                length,
+            } => write!(
+                f,
+                "private chunk {chunk_type} of {length} bytes at offset {offset}"
+            ),
+            Finding::TrailingData { offset, length } => {
+                write!(f, "{length} bytes after IEND at offset {offset}")
+            }
+            Finding::OversizedText {
+                offset,
+                chunk_type,
+                length,
+            } => write!(f, "{chunk_type} chunk of {length} bytes at offset {offset}"),
+            Finding::UnusualIdatSplit { reason, sizes } => {
+                write!(f, "{reason}, IDAT sizes {sizes:?}")
+            }
+            Finding::HighEntropy {
+                offset,
+                chunk_type,
+                entropy,
+            } => write!(
+                f,
+                "{chunk_type} chunk at offset {offset} looks random ({entropy:.2} bits per byte)"
+            ),
+        }
+    }
+}
+
+/// Shannon entropy of `data` in bits per byte.
+pub fn entropy(data: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / data.len() as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+fn is_text(chunk: &ChunkRef<'_>) -> bool {
+    matches!(&chunk.chunk_type().bytes(), b"tEXt" | b"zTXt" | b"iTXt")
+}
+
+/// Checks how the IDAT chunks are laid out.
+///
+/// Encoders write the image data back to back in chunks of one fixed size, with only the last one
+/// allowed to be smaller, so anything else suggests the chunks were edited by hand.
+fn inspect_idat(png: &PngRef<'_>) -> Option<Finding> {
+    let mut sizes = Vec::new();
+    let mut runs = 0;
+    let mut previous_was_idat = false;
+
+    for chunk in png.chunks() {
+        let is_idat = chunk.chunk_type().bytes() == *b"IDAT";
+        if is_idat {
+            sizes.push(chunk.length());
+            if !previous_was_idat {
+                runs += 1;
+            }
+        }
+        previous_was_idat = is_idat;
+    }
+
+    let reason = if runs > 1 {
+        "IDAT chunks are not consecutive"
+    } else if sizes.contains(&0) {
+        "empty IDAT chunk"
+    } else if let Some((last, rest)) = sizes.split_last() {
+        if rest.windows(2).any(|pair| pair[0] != pair[1]) {
+            "IDAT chunks have different sizes"
+        } else if rest.first().is_some_and(|first| last > first) {
+            "last IDAT chunk is larger than the others"
+        } else {
+            return None;
+        }
+    } else {
+        return None;
+    };
+
+    Some(Finding::UnusualIdatSplit { reason, sizes })
+}
+
+/// Looks for places in the file structure where data could be hidden.
+pub fn inspect(png: &PngRef<'_>) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    for (offset, chunk) in png.chunk_offsets() {
+        let chunk_type = *chunk.chunk_type();
+
+        if !chunk_type.is_public() {
+            findings.push(Finding::PrivateChunk {
+                offset,
+                chunk_type,
+                length: chunk.length(),
+            });
+        }
+
+        if is_text(&chunk) && chunk.data().len() > MAX_TEXT_LENGTH {
+            findings.push(Finding::OversizedText {
+                offset,
+                chunk_type,
+                length: chunk.length(),
+            });
+        }
+
+        let data = chunk.data();
+        if !chunk_type.is_critical()
+            && data.len() >= MIN_ENTROPY_LENGTH
+            && !COMPRESSED_CHUNKS.contains(&&chunk_type.bytes())
+        {
+            let entropy = entropy(data);
+            let highest = (data.len().min(256) as f64).log2();
+            if entropy > ENTROPY_RATIO * highest {
+                findings.push(Finding::HighEntropy {
+                    offset,
+                    chunk_type,
+                    entropy,
+                });
+            }
+        }
+    }
+
+    findings.extend(inspect_idat(png));
+
+    if !png.trailing().is_empty() {
+        findings.push(Finding::TrailingData {
+            offset: png.trailing_offset(),
+            length: png.trailing().len(),
+        });
+    }
+
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::{tests::PNG_FILE, Chunk, Png};
+
+    fn testing_bytes(extra: Vec<Chunk>, trailing: &[u8]) -> Vec<u8> {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let mut chunks = png.chunks().to_vec();
+        let iend = chunks.pop().unwrap();
+        chunks.extend(extra);
+        chunks.push(iend);
+
+        [Png::from_chunks(chunks).as_bytes(), trailing.to_vec()].concat()
+    }
+
+    fn chunk(chunk_type: &[u8; 4], data: Vec<u8>) -> Chunk {
+        Chunk::new(ChunkType::from_bytes(*chunk_type), data)
+    }
+
+    /// Data that uses every byte value equally often, like encrypted data does on average.
+    fn random_bytes(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 167 % 256) as u8).collect()
+    }
+
+    #[test]
+    fn test_inspect_image_file() {
+        let png = PngRef::try_from(&PNG_FILE[..]).unwrap();
+        let findings = inspect(&png);
+
+        assert_eq!(
+            findings,
+            [Finding::PrivateChunk {
+                offset: 4776,
+                chunk_type: ChunkType::from_bytes(*b"RuSt"),
+                length: 3,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_trailing_data() {
+        let bytes = testing_bytes(vec![], b"hidden");
+        let png = PngRef::try_from(&bytes[..]).unwrap();
+
+        assert!(inspect(&png).contains(&Finding::TrailingData {
+            offset: PNG_FILE.len(),
+            length: 6,
+        }));
+    }
+
+    #[test]
+    fn test_oversized_text() {
+        let mut text = b"Comment\0".to_vec();
+        text.extend(b"lorem ipsum ".repeat(400));
+        let bytes = testing_bytes(vec![chunk(b"tEXt", text)], &[]);
+        let png = PngRef::try_from(&bytes[..]).unwrap();
+
+        let findings = inspect(&png);
+        assert!(findings
+            .iter()
+            .any(|f| matches!(f, Finding::OversizedText { length: 4808, .. })));
+        assert!(!findings
+            .iter()
+            .any(|f| matches!(f, Finding::HighEntropy { .. })));
+    }
+
+    #[test]
+    fn test_high_entropy() {
+        let bytes = testing_bytes(
+            vec![
+                chunk(b"prVt", random_bytes(1000)),
+                chunk(b"zTXt", random_bytes(1000)),
+            ],
+            &[],
+        );
+        let png = PngRef::try_from(&bytes[..]).unwrap();
+
+        let high_entropy: Vec<String> = inspect(&png)
+            .iter()
+            .filter_map(|f| match f {
+                Finding::HighEntropy { chunk_type, .. } => Some(chunk_type.to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(high_entropy, ["prVt"]);
+    }
+
+    #[test]
+    fn test_idat_split() {
+        let split = |sizes: &[usize], between: Option<Chunk>| {
+            let png = Png::try_from(&PNG_FILE[..]).unwrap();
+            let mut chunks = vec![png.chunks()[0].clone()];
+            for (i, &size) in sizes.iter().enumerate() {
+                if i == 1 {
+                    chunks.extend(between.clone());
+                }
+                chunks.push(chunk(b"IDAT", vec![0; size]));
+            }
+            chunks.push(chunk(b"IEND", vec![]));
+
+            let bytes = Png::from_chunks(chunks).as_bytes();
+            inspect_idat(&PngRef::try_from(&bytes[..]).unwrap())
+        };
+
+        assert_eq!(split(&[100, 100, 50], None), None);
+        assert_eq!(split(&[100], None), None);
+        assert!(split(&[100, 60, 50], None).is_some());
+        assert!(split(&[100, 100, 150], None).is_some());
+        assert!(split(&[100, 0], None).is_some());
+        assert!(split(&[100, 100], Some(chunk(b"tEXt", vec![]))).is_some());
+    }
+
+    #[test]
+    fn test_entropy() {
+        assert_eq!(entropy(&[7; 100]), 0.0);
+        assert_eq!(entropy(&[0, 1, 2, 3]), 2.0);
+        assert_eq!(entropy(&random_bytes(256)), 8.0);
+    }
+}
@@ -0,0 +1,599 @@
+use crate::{
+    image::{EncodeOptions, Ihdr},
+    png::{Chunk, ChunkType, Png},
+};
+use std::{
+    convert::TryFrom,
+    fmt::{self, Display, Formatter},
+};
+
+/// The contents of an acTL chunk, which marks a PNG as animated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationControl {
+    pub num_frames: u32,
+    /// Number of times the animation plays, where 0 means forever.
+    pub num_plays: u32,
+}
+
+impl AnimationControl {
+    pub fn as_bytes(&self) -> Vec<u8> {
+        [self.num_frames.to_be_bytes(), self.num_plays.to_be_bytes()].concat()
+    }
+
+    pub fn as_chunk(&self) -> Chunk {
+        Chunk::new(ChunkType::from_bytes(*b"acTL"), self.as_bytes())
+    }
+}
+
+impl TryFrom<&[u8]> for AnimationControl {
+    type Error = crate::Error;
+
+    fn try_from(value: &[u8]) -> crate::Result<Self> {
+        let value: &[u8; 8] = value
+            .try_into()
+            .map_err(|_| "acTL chunk must be 8 bytes long")?;
+
+        let num_frames = u32::from_be_bytes(value[0..4].try_into()?);
+        if num_frames == 0 {
+            return Err("acTL chunk must declare at least one frame".into());
+        }
+
+        Ok(Self {
+            num_frames,
+            num_plays: u32::from_be_bytes(value[4..8].try_into()?),
+        })
+    }
+}
+
+/// What happens to a frame's region before the next frame is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisposeOp {
+    None = 0,
+    Background = 1,
+    Previous = 2,
+}
+
+impl TryFrom<u8> for DisposeOp {
+    type Error = crate::Error;
+
+    fn try_from(value: u8) -> crate::Result<Self> {
+        match value {
+            0 => Ok(DisposeOp::None),
+            1 => Ok(DisposeOp::Background),
+            2 => Ok(DisposeOp::Previous),
+            _ => Err(format!("invalid dispose op {value}").into()),
+        }
+    }
+}
+
+/// How a frame is drawn over the output buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendOp {
+    Source = 0,
+    Over = 1,
+}
+
+impl TryFrom<u8> for BlendOp {
+    type Error = crate::Error;
+
+    fn try_from(value: u8) -> crate::Result<Self> {
+        match value {
+            0 => Ok(BlendOp::Source),
+            1 => Ok(BlendOp::Over),
+            _ => Err(format!("invalid blend op {value}").into()),
+        }
+    }
+}
+
+/// The contents of an fcTL chunk, which describes one frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameControl {
+    pub sequence_number: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: u32,
+    pub y_offset: u32,
+    pub delay_num: u16,
+    pub delay_den: u16,
+    pub dispose_op: DisposeOp,
+    pub blend_op: BlendOp,
+}
+
+impl FrameControl {
+    /// How long the frame is shown, in seconds. A denominator of 0 means hundredths of a second.
+    pub fn delay(&self) -> f64 {
+        let den = if self.delay_den == 0 {
+            100
+        } else {
+            self.delay_den
+        };
+
+        f64::from(self.delay_num) / f64::from(den)
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        [
+            &self.sequence_number.to_be_bytes()[..],
+            &self.width.to_be_bytes(),
+            &self.height.to_be_bytes(),
+            &self.x_offset.to_be_bytes(),
+            &self.y_offset.to_be_bytes(),
+            &self.delay_num.to_be_bytes(),
+            &self.delay_den.to_be_bytes(),
+            &[self.dispose_op as u8, self.blend_op as u8],
+        ]
+        .concat()
+    }
+
+    pub fn as_chunk(&self) -> Chunk {
+        Chunk::new(ChunkType::from_bytes(*b"fcTL"), self.as_bytes())
+    }
+
+    /// Checks that the frame lies within the canvas described by `ihdr`.
+    fn check_bounds(&self, ihdr: &Ihdr) -> crate::Result<()> {
+        let fits = |offset: u32, size: u32, limit: u32| {
+            offset.checked_add(size).is_some_and(|end| end <= limit)
+        };
+
+        if !fits(self.x_offset, self.width, ihdr.width)
+            || !fits(self.y_offset, self.height, ihdr.height)
+        {
+            return Err(format!(
+                "frame {} lies outside of the {}x{} canvas",
+                self.sequence_number, ihdr.width, ihdr.height
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+impl TryFrom<&[u8]> for FrameControl {
+    type Error = crate::Error;
+
+    fn try_from(value: &[u8]) -> crate::Result<Self> {
+        let value: &[u8; 26] = value
+            .try_into()
+            .map_err(|_| "fcTL chunk must be 26 bytes long")?;
+        let u32_at =
+            |i: usize| u32::from_be_bytes([value[i], value[i + 1], value[i + 2], value[i + 3]]);
+
+        let control = Self {
+            sequence_number: u32_at(0),
+            width: u32_at(4),
+            height: u32_at(8),
+            x_offset: u32_at(12),
+            y_offset: u32_at(16),
+            delay_num: u16::from_be_bytes([value[20], value[21]]),
+            delay_den: u16::from_be_bytes([value[22], value[23]]),
+            dispose_op: DisposeOp::try_from(value[24])?,
+            blend_op: BlendOp::try_from(value[25])?,
+        };
+
+        if control.width == 0 || control.height == 0 {
+            return Err("fcTL chunk has an empty frame".into());
+        }
+
+        Ok(control)
+    }
+}
+
+impl Display for FrameControl {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}x{} at ({}, {}), delay {:.3}s, dispose {:?}, blend {:?}",
+            self.width,
+            self.height,
+            self.x_offset,
+            self.y_offset,
+            self.delay(),
+            self.dispose_op,
+            self.blend_op
+        )
+    }
+}
+
+/// One frame of an animation and its compressed pixels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub control: FrameControl,
+    /// The frame's zlib stream, joined from its IDAT or fdAT chunks without sequence numbers.
+    pub data: Vec<u8>,
+}
+
+/// The frames of an APNG, in display order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Animation {
+    pub control: AnimationControl,
+    pub frames: Vec<Frame>,
+    /// Whether the IDAT image is the first frame, rather than a fallback that isn't shown.
+    pub default_is_frame: bool,
+}
+
+impl Animation {
+    /// Reads the animation chunks of `png`, checking their order and that the fcTL and fdAT
+    /// sequence numbers count up from 0 without gaps.
+    pub fn from_png(png: &Png) -> crate::Result<Self> {
+        let ihdr = png.chunk_by_type("IHDR").ok_or("missing IHDR chunk")?;
+        let ihdr = Ihdr::try_from(ihdr.data())?;
+
+        let mut control = None;
+        let mut frames: Vec<Frame> = Vec::new();
+        let mut default_is_frame = false;
+        let mut seen_idat = false;
+        let mut next_sequence = 0;
+        let mut check_sequence = |sequence_number: u32| {
+            if sequence_number != next_sequence {
+                return Err(format!(
+                    "expected sequence number {next_sequence}, found {sequence_number}"
+                ));
+            }
+            next_sequence += 1;
+            Ok(())
+        };
+
+        for chunk in png.chunks() {
+            match &chunk.chunk_type().bytes() {
+                b"acTL" if seen_idat => {
+                    return Err("acTL chunk must come before the first IDAT chunk".into());
+                }
+                b"acTL" if control.is_some() => return Err("duplicate acTL chunk".into()),
+                b"acTL" => control = Some(AnimationControl::try_from(chunk.data())?),
+                b"fcTL" => {
+                    let frame = FrameControl::try_from(chunk.data())?;
+                    check_sequence(frame.sequence_number)?;
+                    frame.check_bounds(&ihdr)?;
+
+                    if !seen_idat {
+                        if frame.width != ihdr.width
+                            || frame.height != ihdr.height
+                            || frame.x_offset != 0
+                            || frame.y_offset != 0
+                        {
+                            return Err("the first frame must cover the whole canvas".into());
+                        }
+                        default_is_frame = true;
+                    }
+
+                    frames.push(Frame {
+                        control: frame,
+                        data: Vec::new(),
+                    });
+                }
+                b"IDAT" => {
+                    seen_idat = true;
+                    if default_is_frame {
+                        frames[0].data.extend(chunk.data());
+                    }
+                }
+                b"fdAT" => {
+                    let (sequence_number, data) = chunk
+                        .data()
+                        .split_first_chunk::<4>()
+                        .ok_or("fdAT chunk is missing its sequence number")?;
+                    check_sequence(u32::from_be_bytes(*sequence_number))?;
+
+                    // The frame of the default image takes its data from IDAT instead.
+                    let is_default = default_is_frame && frames.len() == 1;
+                    match frames.last_mut() {
+                        Some(frame) if seen_idat && !is_default => frame.data.extend(data),
+                        _ => return Err("fdAT chunk does not follow an fcTL chunk".into()),
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let control = control.ok_or("not an animated PNG, there is no acTL chunk")?;
+        if frames.len() != control.num_frames as usize {
+            return Err(format!(
+                "acTL chunk declares {} frames, but there are {}",
+                control.num_frames,
+                frames.len()
+            )
+            .into());
+        }
+        if let Some(i) = frames.iter().position(|frame| frame.data.is_empty()) {
+            return Err(format!("frame {i} has no image data").into());
+        }
+
+        Ok(Self {
+            control,
+            frames,
+            default_is_frame,
+        })
+    }
+
+    /// Builds a standalone PNG out of frame `index`.
+    ///
+    /// The PNG holds only the frame's own region, as stored in the file, without blending it over
+    /// the frames before it. Chunks that come before IDAT, such as PLTE and tRNS, are kept.
+    pub fn extract(&self, png: &Png, index: usize) -> crate::Result<Png> {
+        let frame = self.frames.get(index).ok_or("there is no such frame")?;
+        let ihdr = png.chunk_by_type("IHDR").ok_or("missing IHDR chunk")?;
+        let ihdr = Ihdr {
+            width: frame.control.width,
+            height: frame.control.height,
+            ..Ihdr::try_from(ihdr.data())?
+        };
+
+        let mut chunks = vec![ihdr.as_chunk()];
+        chunks.extend(
+            png.chunks()
+                .iter()
+                .take_while(|c| c.chunk_type().bytes() != *b"IDAT")
+                .filter(|c| !matches!(&c.chunk_type().bytes(), b"IHDR" | b"acTL" | b"fcTL"))
+                .cloned(),
+        );
+        chunks.extend(
+            frame
+                .data
+                .chunks(EncodeOptions::default().idat_size)
+                .map(|data| Chunk::new(ChunkType::from_bytes(*b"IDAT"), data.to_vec())),
+        );
+        chunks.push(Chunk::new(ChunkType::from_bytes(*b"IEND"), Vec::new()));
+
+        Ok(Png::from_chunks(chunks))
+    }
+
+    /// Replaces the fcTL, fdAT and, if the default image is a frame, IDAT chunks of `png` with
+    /// the frames of this animation.
+    ///
+    /// Frame data may have changed size, so the data is split into new chunks and every sequence
+    /// number is assigned again in order.
+    pub fn write_into(&self, png: &mut Png) -> crate::Result<()> {
+        let idat_size = EncodeOptions::default().idat_size;
+        let mut sequence_number = 0;
+        let mut next_sequence = || {
+            sequence_number += 1;
+            sequence_number - 1
+        };
+
+        let mut frames = self.frames.iter();
+        let mut chunks = Vec::new();
+        if self.default_is_frame {
+            let frame = frames.next().ok_or("animation has no frames")?;
+            let control = FrameControl {
+                sequence_number: next_sequence(),
+                ..frame.control
+            };
+
+            chunks.push(control.as_chunk());
+            chunks.extend(
+                frame
+                    .data
+                    .chunks(idat_size)
+                    .map(|data| Chunk::new(ChunkType::from_bytes(*b"IDAT"), data.to_vec())),
+            );
+        } else {
+            chunks.extend(png.chunks_by_type("IDAT").cloned());
+        }
+
+        for frame in frames {
+            let control = FrameControl {
+                sequence_number: next_sequence(),
+                ..frame.control
+            };
+
+            chunks.push(control.as_chunk());
+            for data in frame.data.chunks(idat_size) {
+                let data = [&next_sequence().to_be_bytes()[..], data].concat();
+                chunks.push(Chunk::new(ChunkType::from_bytes(*b"fdAT"), data));
+            }
+        }
+
+        png.retain_chunks(|c| !matches!(&c.chunk_type().bytes(), b"fcTL" | b"fdAT"));
+        png.replace_chunks("IDAT", chunks)
+    }
+}
+
+/// Builds an APNG that shows `pngs` in order, each for `delay` milliseconds.
+///
+/// Every frame must have the same IHDR and palette as the first, whose other chunks are kept.
+pub fn assemble(pngs: &[Png], delay: u16, num_plays: u32) -> crate::Result<Png> {
+    let first = pngs.first().ok_or("there are no frames to assemble")?;
+    let ihdr = first.chunk_by_type("IHDR").ok_or("missing IHDR chunk")?;
+    let (ihdr_data, ihdr) = (ihdr.data(), Ihdr::try_from(ihdr.data())?);
+    let palette = first.chunk_by_type("PLTE").map(Chunk::data);
+
+    for (i, png) in pngs.iter().enumerate() {
+        if png.chunk_by_type("IHDR").map(Chunk::data) != Some(ihdr_data) {
+            return Err(format!("frame {i} has a different size or pixel format").into());
+        }
+        if png.chunk_by_type("PLTE").map(Chunk::data) != palette {
+            return Err(format!("frame {i} has a different palette").into());
+        }
+        if png.chunk_by_type("IDAT").is_none() {
+            return Err(format!("frame {i} has no image data").into());
+        }
+    }
+
+    let frame_control = |sequence_number| FrameControl {
+        sequence_number,
+        width: ihdr.width,
+        height: ihdr.height,
+        x_offset: 0,
+        y_offset: 0,
+        delay_num: delay,
+        delay_den: 1000,
+        dispose_op: DisposeOp::None,
+        blend_op: BlendOp::Source,
+    };
+    let animation = AnimationControl {
+        num_frames: u32::try_from(pngs.len()).map_err(|_| "too many frames")?,
+        num_plays,
+    };
+
+    let is_idat = |c: &&Chunk| c.chunk_type().bytes() == *b"IDAT";
+    let kept: Vec<&Chunk> = first
+        .chunks()
+        .iter()
+        .filter(|c| !matches!(&c.chunk_type().bytes(), b"acTL" | b"fcTL" | b"fdAT"))
+        .collect();
+    let start = kept.iter().position(is_idat).unwrap_or_default();
+    let end = kept.iter().rposition(is_idat).map_or(start, |i| i + 1);
+
+    // The first PNG's IDAT chunks become the first frame, and the other frames follow them.
+    let mut output: Vec<Chunk> = kept[..start].iter().copied().cloned().collect();
+    output.push(animation.as_chunk());
+
+    let mut sequence_number = 0;
+    for (i, png) in pngs.iter().enumerate() {
+        output.push(frame_control(sequence_number).as_chunk());
+        sequence_number += 1;
+
+        if i == 0 {
+            output.extend(kept[start..end].iter().copied().cloned());
+            continue;
+        }
+
+        for idat in png.chunks_by_type("IDAT") {
+            let data = [&sequence_number.to_be_bytes()[..], idat.data()].concat();
+            output.push(Chunk::new(ChunkType::from_bytes(*b"fdAT"), data));
+            sequence_number += 1;
+        }
+    }
+
+    output.extend(kept[end..].iter().copied().cloned());
+
+    Ok(Png::from_chunks(output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        image::Image,
+        png::{tests::PNG_FILE, Limits},
+    };
+
+    /// Three copies of the test image with different pixels, with small IDAT chunks so that
+    /// every frame needs several fdAT chunks.
+    fn testing_frames() -> Vec<Png> {
+        (0..3u8)
+            .map(|i| {
+                let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+                let mut image = Image::decode(&png, &Limits::default()).unwrap();
+                image.pixels_mut().iter_mut().for_each(|p| *p ^= i * 40);
+
+                let options = EncodeOptions {
+                    idat_size: 1000,
+                    ..EncodeOptions::default()
+                };
+                image.encode_into(&mut png, &options).unwrap();
+                png
+            })
+            .collect()
+    }
+
+    fn round_trip(png: &Png) -> Png {
+        Png::try_from(&png.as_bytes()[..]).unwrap()
+    }
+
+    #[test]
+    fn test_assemble_and_extract() {
+        let frames = testing_frames();
+        let apng = round_trip(&assemble(&frames, 40, 2).unwrap());
+        let animation = Animation::from_png(&apng).unwrap();
+
+        assert_eq!(
+            animation.control,
+            AnimationControl {
+                num_frames: 3,
+                num_plays: 2
+            }
+        );
+        assert!(animation.default_is_frame);
+
+        for (i, frame) in animation.frames.iter().enumerate() {
+            assert_eq!(frame.control.delay(), 0.04);
+
+            let extracted = round_trip(&animation.extract(&apng, i).unwrap());
+            assert_eq!(
+                Image::decode(&extracted, &Limits::default()).unwrap(),
+                Image::decode(&frames[i], &Limits::default()).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_sequence_numbers_validated() {
+        let apng = assemble(&testing_frames(), 40, 0).unwrap();
+
+        let mut chunks = apng.chunks().to_vec();
+        let fdat = chunks
+            .iter()
+            .rposition(|c| c.chunk_type().bytes() == *b"fdAT")
+            .unwrap();
+        let mut data = chunks[fdat].data().to_vec();
+        data[3] += 1;
+        chunks[fdat] = Chunk::new(ChunkType::from_bytes(*b"fdAT"), data);
+
+        let err = Animation::from_png(&Png::from_chunks(chunks)).unwrap_err();
+        assert!(err.to_string().contains("sequence number"));
+    }
+
+    #[test]
+    fn test_write_into() {
+        let frames = testing_frames();
+        let mut apng = assemble(&frames, 40, 0).unwrap();
+        let mut animation = Animation::from_png(&apng).unwrap();
+
+        // A frame that compresses much better needs fewer fdAT chunks afterwards.
+        let image = Image::new(
+            *Image::decode(&frames[0], &Limits::default())
+                .unwrap()
+                .ihdr(),
+            vec![0; 50 * 50 * 4],
+        )
+        .unwrap();
+        animation.frames[1].data = image
+            .encode(&EncodeOptions::default())
+            .unwrap()
+            .iter()
+            .flat_map(|c| c.data().to_vec())
+            .collect();
+        animation.write_into(&mut apng).unwrap();
+
+        let apng = round_trip(&apng);
+        let rewritten = Animation::from_png(&apng).unwrap();
+        assert_eq!(rewritten.frames.len(), 3);
+        assert_eq!(
+            Image::decode(&rewritten.extract(&apng, 1).unwrap(), &Limits::default()).unwrap(),
+            image
+        );
+        assert_eq!(
+            Image::decode(&rewritten.extract(&apng, 2).unwrap(), &Limits::default()).unwrap(),
+            Image::decode(&frames[2], &Limits::default()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_frame_control() {
+        let bytes = [
+            0, 0, 0, 1, 0, 0, 0, 10, 0, 0, 0, 20, 0, 0, 0, 5, 0, 0, 0, 6, 0, 3, 0, 0, 1, 1,
+        ];
+        let control = FrameControl::try_from(&bytes[..]).unwrap();
+
+        assert_eq!(control.as_bytes(), bytes);
+        assert_eq!(control.delay(), 0.03);
+        assert_eq!(control.dispose_op, DisposeOp::Background);
+        assert_eq!(control.blend_op, BlendOp::Over);
+        assert_eq!(
+            control.to_string(),
+            "10x20 at (5, 6), delay 0.030s, dispose Background, blend Over"
+        );
+
+        let mut bad = bytes;
+        bad[24] = 3;
+        assert!(FrameControl::try_from(&bad[..]).is_err());
+    }
+
+    #[test]
+    fn test_not_animated() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert!(Animation::from_png(&png).is_err());
+    }
+}
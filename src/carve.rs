@@ -0,0 +1,87 @@
+use crate::png::{Format, Limits, PngRef};
+
+/// A complete PNG found inside another file.
+#[derive(Debug, Clone, Copy)]
+pub struct Carved<'a> {
+    /// Where the PNG signature starts in the scanned file.
+    pub offset: usize,
+    /// The PNG, from its signature up to and including its IEND chunk.
+    pub bytes: &'a [u8],
+    pub png: PngRef<'a>,
+}
+
+/// Finds every complete PNG in `bytes`, in order of their offsets.
+///
+/// Each PNG signature is parsed forward with the chunk reader, and only those that reach an IEND
+/// chunk with every CRC intact are returned. Signatures inside another carved PNG are followed
+/// too, since a PNG hidden in a chunk of another one is exactly what carving should turn up.
+pub fn carve<'a>(bytes: &'a [u8], limits: &Limits) -> Vec<Carved<'a>> {
+    let signature = Format::Png.signature();
+
+    (0..bytes.len())
+        .filter(|&offset| bytes[offset..].starts_with(signature))
+        .filter_map(|offset| {
+            let png = PngRef::parse_with_limits(&bytes[offset..], limits).ok()?;
+            if png.chunks().last()?.chunk_type().bytes() != *b"IEND" {
+                return None;
+            }
+
+            // Parsing again without whatever followed the PNG leaves it with no trailing data.
+            let bytes = &bytes[offset..offset + png.trailing_offset()];
+            let png = PngRef::parse_with_limits(bytes, limits).ok()?;
+
+            Some(Carved { offset, bytes, png })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        analyze::{self, Finding},
+        png::{tests::PNG_FILE, Chunk, ChunkType, Png},
+    };
+
+    #[test]
+    fn test_carve() {
+        // A second PNG hidden in a private chunk of a third one.
+        let mut outer = Png::try_from(&PNG_FILE[..]).unwrap();
+        let hidden = Chunk::new(ChunkType::from_bytes(*b"prVt"), PNG_FILE.to_vec());
+        outer.insert_chunk_before("IEND", hidden).unwrap();
+        let outer = outer.as_bytes();
+
+        let truncated = &PNG_FILE[..PNG_FILE.len() - 20];
+        let blob = [
+            &b"%PDF-1.7 garbage"[..],
+            &PNG_FILE,
+            &[0; 100],
+            truncated,
+            &outer,
+            b"more garbage",
+        ]
+        .concat();
+
+        let carved = carve(&blob, &Limits::default());
+        let offsets: Vec<usize> = carved.iter().map(|c| c.offset).collect();
+        let first = 16;
+        let outer_offset = first + PNG_FILE.len() + 100 + truncated.len();
+
+        // The hidden PNG ends right before the CRC of its chunk and the outer IEND chunk.
+        let hidden_offset = outer_offset + outer.len() - 12 - PNG_FILE.len() - 4;
+        assert_eq!(offsets, [first, outer_offset, hidden_offset]);
+
+        assert_eq!(carved[0].bytes, PNG_FILE);
+        assert_eq!(carved[1].bytes, outer);
+        assert!(carved[1].png.trailing().is_empty());
+        assert!(analyze::inspect(&carved[1].png)
+            .iter()
+            .any(|finding| matches!(finding, Finding::PrivateChunk { .. })));
+    }
+
+    #[test]
+    fn test_nothing_to_carve() {
+        assert!(carve(b"no PNG in here", &Limits::default()).is_empty());
+        assert!(carve(&PNG_FILE[..100], &Limits::default()).is_empty());
+    }
+}
@@ -1,20 +1,24 @@
 use crate::chunk_type::ChunkType;
-use crc::{Crc, CRC_32_ISO_HDLC};
+use crc::{Crc, Table, CRC_32_ISO_HDLC};
 use std::{
     convert::TryFrom,
     fmt::{self, Display, Formatter},
-    io::{BufReader, Cursor, Read, Seek, SeekFrom},
 };
 
-#[derive(Debug)]
+/// CRC-32 used by PNG, computed with a slice-by-16 lookup table that is built at compile time.
+static CRC: Crc<u32, Table<16>> = Crc::<u32, Table<16>>::new(&CRC_32_ISO_HDLC);
+
+#[derive(Debug, Clone)]
 pub struct Chunk {
     ctype: ChunkType,
     cdata: Vec<u8>,
+    crc: u32,
 }
 
 impl Chunk {
     pub fn new(ctype: ChunkType, cdata: Vec<u8>) -> Self {
-        Self { ctype, cdata }
+        let crc = checksum(&ctype, &cdata);
+        Self { ctype, cdata, crc }
     }
 
     fn length(&self) -> u32 {
@@ -25,14 +29,13 @@ impl Chunk {
         &self.ctype
     }
 
-    fn data(&self) -> &[u8] {
+    pub fn data(&self) -> &[u8] {
         &self.cdata
     }
 
-    fn crc(&self) -> u32 {
-        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
-        let td_bytes = [self.chunk_type().bytes().as_slice(), self.data()].concat();
-        crc.checksum(td_bytes.as_ref())
+    /// The CRC of the chunk type and data, computed once when the chunk is created.
+    pub fn crc(&self) -> u32 {
+        self.crc
     }
 
     pub fn data_as_string(&self) -> crate::Result<String> {
@@ -40,7 +43,14 @@ impl Chunk {
     }
 
     pub fn as_bytes(&self) -> Vec<u8> {
-        [self.chunk_type().bytes().as_slice(), self.data()].concat()
+        self.length()
+            .to_be_bytes()
+            .iter()
+            .chain(self.chunk_type().bytes().iter())
+            .chain(self.data().iter())
+            .chain(self.crc().to_be_bytes().iter())
+            .copied()
+            .collect()
     }
 }
 
@@ -48,34 +58,112 @@ impl TryFrom<&[u8]> for Chunk {
     type Error = crate::Error;
 
     fn try_from(value: &[u8]) -> crate::Result<Self> {
-        let mut reader = BufReader::new(Cursor::new(value));
+        ChunkRef::try_from(value).map(Chunk::from)
+    }
+}
+
+impl From<ChunkRef<'_>> for Chunk {
+    fn from(chunk: ChunkRef<'_>) -> Self {
+        // The stored CRC is kept as-is, so a chunk from `parse_prefix` round-trips byte for byte.
+        Self {
+            ctype: chunk.ctype,
+            cdata: chunk.cdata.to_vec(),
+            crc: chunk.crc,
+        }
+    }
+}
+
+impl Display for Chunk {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // Borrowing the chunk keeps the output identical to that of `ChunkRef`.
+        let chunk = ChunkRef {
+            ctype: self.ctype,
+            cdata: &self.cdata,
+            crc: self.crc,
+        };
+        chunk.fmt(f)
+    }
+}
+
+/// A chunk whose data is borrowed from the buffer it was parsed from.
+///
+/// Parsing a `ChunkRef` never copies the chunk data, which makes it suitable for scanning large
+/// files. Use `Chunk::from` to get an owned copy.
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkRef<'a> {
+    ctype: ChunkType,
+    cdata: &'a [u8],
+    crc: u32,
+}
+
+impl<'a> ChunkRef<'a> {
+    /// Number of bytes used by the length, chunk type and CRC fields of every chunk.
+    pub const OVERHEAD: usize = 12;
 
-        let mut ctype_buf: [u8; 4] = [0, 0, 0, 0];
-        reader.seek(SeekFrom::Start(4))?;
-        reader.read_exact(&mut ctype_buf)?;
-        let ctype = ChunkType::try_from(ctype_buf)?;
+    /// Parses the chunk at the start of `bytes` without verifying its CRC.
+    ///
+    /// Any bytes after the end of the chunk are ignored.
+    pub fn parse_prefix(bytes: &'a [u8]) -> crate::Result<Self> {
+        if bytes.len() < Self::OVERHEAD {
+            return Err("not enough bytes for a chunk".into());
+        }
+
+        let length = u32::from_be_bytes(bytes[0..4].try_into()?) as usize;
+        let data_end = length
+            .checked_add(8)
+            .filter(|end| end + 4 <= bytes.len())
+            .ok_or("chunk length exceeds the available bytes")?;
+
+        let ctype = ChunkType::try_from(<[u8; 4]>::try_from(&bytes[4..8])?)?;
+        let cdata = &bytes[8..data_end];
+        let crc = u32::from_be_bytes(bytes[data_end..data_end + 4].try_into()?);
+
+        Ok(Self { ctype, cdata, crc })
+    }
 
-        reader.rewind()?;
+    /// Parses the chunk at the start of `bytes` and verifies its CRC.
+    ///
+    /// Any bytes after the end of the chunk are ignored.
+    pub fn from_prefix(bytes: &'a [u8]) -> crate::Result<Self> {
+        let chunk = Self::parse_prefix(bytes)?;
+        chunk.verify_crc()?;
 
-        let cdata_handle = &mut &value[8..value.len() - 4];
-        let mut cdata = Vec::new();
-        cdata_handle.read_to_end(&mut cdata)?;
+        Ok(chunk)
+    }
 
-        let mut crc_buf: [u8; 4] = [0, 0, 0, 0];
-        reader.seek(SeekFrom::End(-4))?;
-        reader.read_exact(&mut crc_buf)?;
-        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
-        let td_bytes = [ctype_buf.as_slice(), cdata.as_slice()].concat();
+    pub fn length(&self) -> u32 {
+        self.cdata.len() as u32
+    }
 
-        if crc.checksum(td_bytes.as_ref()) != u32::from_be_bytes(crc_buf) {
+    pub fn chunk_type(&self) -> &ChunkType {
+        &self.ctype
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.cdata
+    }
+
+    /// The CRC stored in the chunk, which may not match its contents unless the chunk was
+    /// created with `from_prefix` or `try_from`.
+    pub fn crc(&self) -> u32 {
+        self.crc
+    }
+
+    /// Total number of bytes the chunk takes up in its buffer.
+    pub fn total_len(&self) -> usize {
+        self.cdata.len() + Self::OVERHEAD
+    }
+
+    pub fn verify_crc(&self) -> crate::Result<()> {
+        if checksum(&self.ctype, self.cdata) != self.crc {
             return Err("crc does not match".into());
         }
 
-        Ok(Chunk::new(ctype, cdata))
+        Ok(())
     }
 }
 
-impl Display for Chunk {
+impl Display for ChunkRef<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         writeln!(f, "Length: {}", self.length())?;
         writeln!(f, "Type: {}", self.chunk_type())?;
@@ -86,6 +174,28 @@ impl Display for Chunk {
     }
 }
 
+impl<'a> TryFrom<&'a [u8]> for ChunkRef<'a> {
+    type Error = crate::Error;
+
+    fn try_from(value: &'a [u8]) -> crate::Result<Self> {
+        let chunk = Self::from_prefix(value)?;
+
+        if chunk.total_len() != value.len() {
+            return Err("chunk length does not match the number of bytes".into());
+        }
+
+        Ok(chunk)
+    }
+}
+
+/// Computes the CRC of a chunk incrementally over its type and data.
+fn checksum(ctype: &ChunkType, cdata: &[u8]) -> u32 {
+    let mut digest = CRC.digest();
+    digest.update(&ctype.bytes());
+    digest.update(cdata);
+    digest.finalize()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -214,4 +324,63 @@ mod tests {
 
         let _chunk_string = format!("{}", chunk);
     }
+
+    #[test]
+    fn test_chunk_as_bytes_round_trip() {
+        let chunk = testing_chunk();
+        let bytes = chunk.as_bytes();
+        let parsed = Chunk::try_from(bytes.as_ref()).unwrap();
+
+        assert_eq!(bytes.len(), 42 + ChunkRef::OVERHEAD);
+        assert_eq!(parsed.data(), chunk.data());
+        assert_eq!(parsed.crc(), 2882656334);
+    }
+
+    #[test]
+    fn test_chunk_ref_borrows_data() {
+        let bytes = testing_chunk().as_bytes();
+        let chunk = ChunkRef::try_from(bytes.as_ref()).unwrap();
+
+        assert_eq!(chunk.length(), 42);
+        assert_eq!(chunk.chunk_type().to_string(), "RuSt");
+        assert_eq!(chunk.crc(), 2882656334);
+        assert!(std::ptr::eq(chunk.data().as_ptr(), bytes[8..].as_ptr()));
+    }
+
+    #[test]
+    fn test_chunk_ref_from_prefix() {
+        let mut bytes = testing_chunk().as_bytes();
+        bytes.extend_from_slice(b"trailing");
+
+        let chunk = ChunkRef::from_prefix(&bytes).unwrap();
+        assert_eq!(chunk.total_len(), bytes.len() - 8);
+        assert!(ChunkRef::try_from(bytes.as_ref()).is_err());
+    }
+
+    #[test]
+    fn test_chunk_ref_truncated() {
+        let bytes = testing_chunk().as_bytes();
+
+        assert!(ChunkRef::from_prefix(&bytes[..bytes.len() - 1]).is_err());
+        assert!(ChunkRef::from_prefix(&bytes[..4]).is_err());
+    }
+
+    #[test]
+    fn test_chunk_ref_into_chunk() {
+        let bytes = testing_chunk().as_bytes();
+        let chunk = Chunk::from(ChunkRef::try_from(bytes.as_ref()).unwrap());
+
+        assert_eq!(chunk.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_chunk_crc_matches_bytewise() {
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i * 31 % 251) as u8).collect();
+        let chunk = Chunk::new(ChunkType::from_str("IDAT").unwrap(), data.clone());
+
+        let bytewise = Crc::<u32, Table<1>>::new(&CRC_32_ISO_HDLC);
+        let expected = bytewise.checksum(&[b"IDAT".as_slice(), &data].concat());
+
+        assert_eq!(chunk.crc(), expected);
+    }
 }
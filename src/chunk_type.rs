@@ -5,7 +5,7 @@ use std::{
     str::{self, FromStr},
 };
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ChunkType {
     inner: u32,
 }
@@ -15,11 +15,18 @@ fn fifth_bit_check(byte: u8, set: bool) -> bool {
 }
 
 impl ChunkType {
+    /// Creates a chunk type from bytes that are known to be valid, such as a literal.
+    pub const fn from_bytes(bytes: [u8; 4]) -> Self {
+        Self {
+            inner: u32::from_be_bytes(bytes),
+        }
+    }
+
     pub fn bytes(&self) -> [u8; 4] {
         self.inner.to_be_bytes()
     }
 
-    fn is_valid(&self) -> bool {
+    pub fn is_valid(&self) -> bool {
         // There are a few requirements for a valid chunk type:
         // - must be represented by 4 characters (or bytes)
         // - must only contain alphabetic characters
@@ -30,22 +37,22 @@ impl ChunkType {
             && s.chars().nth(2).is_some_and(char::is_uppercase)
     }
 
-    fn is_critical(&self) -> bool {
+    pub fn is_critical(&self) -> bool {
         // If the 5th bit of the 1st byte is not set, the chunk is critical.
         fifth_bit_check(self.bytes()[0], false)
     }
 
-    fn is_public(&self) -> bool {
+    pub fn is_public(&self) -> bool {
         // If the 5th bit of the 2nd byte is not set, the chunk is public.
         fifth_bit_check(self.bytes()[1], false)
     }
 
-    fn is_reserved_bit_valid(&self) -> bool {
+    pub fn is_reserved_bit_valid(&self) -> bool {
         // If the 5th bit of the 3rd byte is not set, the chunk is reserved.
         fifth_bit_check(self.bytes()[2], false)
     }
 
-    fn is_safe_to_copy(&self) -> bool {
+    pub fn is_safe_to_copy(&self) -> bool {
         // If the 5th bit of the 4th byte is set, the chunk is safe to copy.
         fifth_bit_check(self.bytes()[3], true)
     }
@@ -55,6 +62,10 @@ impl TryFrom<[u8; 4]> for ChunkType {
     type Error = crate::Error;
 
     fn try_from(value: [u8; 4]) -> crate::Result<Self> {
+        if !value.iter().all(u8::is_ascii_alphabetic) {
+            return Err("chunk type must only contain ASCII letters".into());
+        }
+
         Ok(Self {
             inner: u32::from_be_bytes(value),
         })
@@ -172,6 +183,12 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    pub fn test_chunk_type_from_non_ascii_bytes() {
+        assert!(ChunkType::try_from([82, 117, 83, 0xFF]).is_err());
+        assert!(ChunkType::from_str("Ru\u{e9}").is_err());
+    }
+
     #[test]
     pub fn test_chunk_type_string() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
@@ -0,0 +1,70 @@
+use crate::keyed::ROUNDS;
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use sha2::Sha256;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+fn key(passphrase: &str, salt: &[u8]) -> Key {
+    let mut key = Key::default();
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, ROUNDS, &mut key);
+    key
+}
+
+/// Encrypts and authenticates `plaintext` with ChaCha20-Poly1305 under a key derived from
+/// `passphrase`.
+///
+/// The random salt and nonce are stored in front of the ciphertext, so encrypting the same
+/// message twice gives unrelated results.
+pub fn encrypt(passphrase: &str, plaintext: &[u8]) -> crate::Result<Vec<u8>> {
+    let mut salt = [0; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+    let ciphertext = ChaCha20Poly1305::new(&key(passphrase, &salt))
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| "encryption failed")?;
+
+    Ok([&salt[..], &nonce, &ciphertext].concat())
+}
+
+/// Reverses `encrypt`, failing if the passphrase is wrong or the data was changed.
+pub fn decrypt(passphrase: &str, sealed: &[u8]) -> crate::Result<Vec<u8>> {
+    if sealed.len() < SALT_LEN + NONCE_LEN + TAG_LEN {
+        return Err("encrypted message is too short".into());
+    }
+
+    let (salt, rest) = sealed.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    Ok(ChaCha20Poly1305::new(&key(passphrase, salt))
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "wrong passphrase or corrupted message")?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let sealed = encrypt("passphrase", b"attack at dawn").unwrap();
+
+        assert_eq!(sealed.len(), SALT_LEN + NONCE_LEN + 14 + TAG_LEN);
+        assert_eq!(decrypt("passphrase", &sealed).unwrap(), b"attack at dawn");
+        assert!(decrypt("wrong", &sealed).is_err());
+    }
+
+    #[test]
+    fn test_tampering_detected() {
+        let mut sealed = encrypt("passphrase", b"attack at dawn").unwrap();
+        sealed[SALT_LEN + NONCE_LEN] ^= 1;
+
+        assert!(decrypt("passphrase", &sealed).is_err());
+        assert!(decrypt("passphrase", &sealed[..20]).is_err());
+    }
+}
@@ -1,19 +1,233 @@
-use crate::{png::*, Result};
-use clap::{Args, Parser, Subcommand as ClapSubcommand};
-use std::{convert::TryFrom, fs, path::PathBuf, str::FromStr};
+use crate::{
+    analyze,
+    apng::{self, Animation},
+    carve, cipher,
+    container::{self, SealOptions},
+    diff,
+    fec::{self, FecOptions},
+    frame_lsb,
+    ico::{self, Icon},
+    image::{EncodeOptions, Image},
+    lsb::{self, EmbedReport, LsbOptions},
+    palette::{self, Palette},
+    png::*,
+    slack, steganalysis,
+    strip::{self, StripOptions},
+    text::{self, TextChunk, TextKind},
+    transplant::{self, TransplantOptions},
+    Result,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use clap::{Args, Parser, Subcommand as ClapSubcommand, ValueEnum};
+use memmap2::Mmap;
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 #[derive(Parser)]
 pub struct Cli {
+    #[command(flatten)]
+    pub limits: LimitArgs,
+
     #[command(subcommand)]
     pub subcommand: Subcommand,
 }
 
+/// Limits applied while parsing input files, which default to `Limits::default()`.
+#[derive(Args)]
+pub struct LimitArgs {
+    /// Largest chunk length to accept
+    #[arg(long, global = true)]
+    max_chunk_length: Option<u32>,
+
+    /// Largest number of chunks to accept
+    #[arg(long, global = true)]
+    max_chunks: Option<usize>,
+
+    /// Largest size in bytes a compressed stream may inflate to
+    #[arg(long, global = true)]
+    max_decompressed_size: Option<usize>,
+
+    /// Largest input file size in bytes
+    #[arg(long, global = true)]
+    max_file_size: Option<u64>,
+}
+
+impl From<&LimitArgs> for Limits {
+    fn from(args: &LimitArgs) -> Self {
+        let defaults = Limits::default();
+
+        Limits {
+            max_chunk_length: args.max_chunk_length.unwrap_or(defaults.max_chunk_length),
+            max_chunks: args.max_chunks.unwrap_or(defaults.max_chunks),
+            max_decompressed_size: args
+                .max_decompressed_size
+                .unwrap_or(defaults.max_decompressed_size),
+            max_file_size: args.max_file_size.unwrap_or(defaults.max_file_size),
+        }
+    }
+}
+
 #[derive(ClapSubcommand)]
 pub enum Subcommand {
     Encode(EncodeArgs),
     Decode(DecodeArgs),
     Remove(RemoveArgs),
     Print(PrintArgs),
+    Validate(ValidateArgs),
+    Analyze(AnalyzeArgs),
+    Frames(FramesArgs),
+    Carve(CarveArgs),
+    Diff(DiffArgs),
+    Strip(StripArgs),
+    Transplant(TransplantArgs),
+}
+
+/// Where in the PNG file a message is hidden.
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Method {
+    /// In a chunk of its own
+    #[default]
+    Chunk,
+    /// In the least significant bits of the pixel samples
+    Lsb,
+    /// In the palette of an indexed-color image
+    Palette,
+    /// After the IEND chunk
+    Trailer,
+    /// In a text chunk under an ordinary keyword
+    Text,
+    /// After the end of the zlib stream inside the IDAT chunks
+    Slack,
+    /// In the least significant bits of the frames of an animated PNG, after the first
+    Frames,
+}
+
+/// How the palette method hides a message.
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum PaletteMode {
+    /// Sort the palette by luminance and use the low bits of the indices, with the LSB settings
+    #[default]
+    Sort,
+    /// Reorder the used palette entries and recolor the unused ones, leaving pixels unchanged
+    Order,
+}
+
+/// Settings for the LSB method, which must be the same when encoding and decoding.
+#[derive(Args)]
+pub struct LsbArgs {
+    /// Number of low bits of each channel used by the LSB method
+    #[arg(long, default_value_t = 1)]
+    bits: u8,
+
+    /// Channels used by the LSB method, any of r, g, b and a
+    #[arg(long, default_value = "rgb")]
+    channels: String,
+
+    /// Passphrase that scatters the message over the image instead of writing it in order
+    #[arg(long)]
+    key: Option<String>,
+
+    /// Use matrix embedding, hiding K bits in every 2^K-1 samples while changing at most one
+    #[arg(long, value_name = "K")]
+    matrix: Option<u8>,
+}
+
+impl LsbArgs {
+    fn options(&self) -> Result<LsbOptions> {
+        Ok(LsbOptions {
+            bits: self.bits,
+            channels: self.channels.parse()?,
+            passphrase: self.key.clone(),
+            matrix: self.matrix,
+        })
+    }
+}
+
+/// Kind of text chunk written by the text method.
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum TextChunkKind {
+    /// Uncompressed Latin-1 text
+    #[default]
+    #[value(name = "tEXt")]
+    Text,
+    /// Compressed Latin-1 text
+    #[value(name = "zTXt")]
+    Ztxt,
+    /// UTF-8 text
+    #[value(name = "iTXt")]
+    Itxt,
+}
+
+impl From<TextChunkKind> for TextKind {
+    fn from(kind: TextChunkKind) -> Self {
+        match kind {
+            TextChunkKind::Text => TextKind::Plain,
+            TextChunkKind::Ztxt => TextKind::Compressed,
+            TextChunkKind::Itxt => TextKind::International,
+        }
+    }
+}
+
+/// Settings for the text method, which must be the same when encoding and decoding.
+#[derive(Args)]
+pub struct TextArgs {
+    /// Keyword of the text chunk used by the text method
+    #[arg(long, default_value = "Comment")]
+    keyword: String,
+
+    /// Base64-encode the message in the text chunk
+    #[arg(long)]
+    base64: bool,
+}
+
+impl TextArgs {
+    /// Turns a message into the text stored in the chunk, encrypting it if there is a
+    /// passphrase.
+    fn seal(&self, message: &[u8], passphrase: Option<&str>) -> Result<String> {
+        match passphrase {
+            Some(passphrase) => Ok(BASE64.encode(cipher::encrypt(passphrase, message)?)),
+            None if self.base64 => Ok(BASE64.encode(message)),
+            None => Ok(String::from_utf8(message.to_vec())?),
+        }
+    }
+
+    /// Recovers the message from the text stored in the chunk.
+    fn open(&self, text: &str, passphrase: Option<&str>) -> Result<Vec<u8>> {
+        match passphrase {
+            Some(passphrase) => cipher::decrypt(passphrase, &BASE64.decode(text)?),
+            None if self.base64 => Ok(BASE64.decode(text)?),
+            None => Ok(text.as_bytes().to_vec()),
+        }
+    }
+}
+
+/// Encryption of the message, which the chunk and text methods support.
+#[derive(Args)]
+pub struct PassphraseArgs {
+    /// Encrypt the message with this passphrase, for the chunk and text methods. For the text
+    /// method this implies --base64
+    #[arg(long)]
+    passphrase: Option<String>,
+}
+
+impl PassphraseArgs {
+    /// The passphrase, refused for methods that would store the message unencrypted anyway.
+    fn for_method(&self, method: Method) -> Result<Option<&str>> {
+        match (&self.passphrase, method) {
+            (None, _) | (Some(_), Method::Chunk | Method::Text) => Ok(self.passphrase.as_deref()),
+            (Some(_), method) => Err(format!(
+                "the {} method doesn't support --passphrase",
+                method
+                    .to_possible_value()
+                    .ok_or("unknown method")?
+                    .get_name()
+            )
+            .into()),
+        }
+    }
 }
 
 /// Encodes a secret message into the PNG file.
@@ -22,25 +236,103 @@ pub struct EncodeArgs {
     /// Path to PNG file
     png_path: PathBuf,
 
-    /// PNG chunk type
-    chunk_type: String,
+    /// PNG chunk type, only given for the chunk method
+    chunk_type: Option<String>,
 
     /// Message to encode
-    message: String,
+    message: Option<String>,
+
+    /// Where to hide the message
+    #[arg(short, long, value_enum, default_value_t)]
+    method: Method,
+
+    #[command(flatten)]
+    lsb: LsbArgs,
+
+    /// How the palette method hides the message
+    #[arg(long, value_enum, default_value_t)]
+    palette: PaletteMode,
+
+    #[command(flatten)]
+    text: TextArgs,
+
+    #[command(flatten)]
+    encryption: PassphraseArgs,
+
+    /// Kind of text chunk the text method writes
+    #[arg(long, value_enum, default_value_t)]
+    text_chunk: TextChunkKind,
+
+    /// Compress the message before the chunk method stores it
+    #[arg(long)]
+    compress: bool,
+
+    /// Add this many Reed–Solomon parity chunks, so the chunk method's message survives that many
+    /// chunks being lost or damaged
+    #[arg(long, value_name = "M")]
+    parity: Option<u8>,
+
+    /// Number of chunks the chunk method splits the message over when --parity is given
+    #[arg(long, value_name = "K", default_value_t = 4, requires = "parity")]
+    shards: u8,
 
     /// Optional path to a file where the result will be outputted
     #[arg(short, long)]
     output: Option<PathBuf>,
+
+    /// Entry of an ICO or CUR file to work on, the first PNG entry by default
+    #[arg(long, value_name = "N")]
+    entry: Option<usize>,
 }
 
-/// Encodes a secret message into the PNG file.
+impl EncodeArgs {
+    /// Sorts out the positional arguments, since only the chunk method takes a chunk type.
+    fn chunk_type_and_message(&self) -> Result<(Option<&str>, &str)> {
+        match (
+            self.method,
+            self.chunk_type.as_deref(),
+            self.message.as_deref(),
+        ) {
+            (Method::Chunk, Some(chunk_type), Some(message)) => Ok((Some(chunk_type), message)),
+            (Method::Chunk, _, _) => {
+                Err("the chunk method needs a chunk type and a message".into())
+            }
+            (_, Some(message), None) => Ok((None, message)),
+            (_, Some(_), Some(_)) => Err("only the chunk method takes a chunk type".into()),
+            (_, None, _) => Err("missing the message to encode".into()),
+        }
+    }
+}
+
+/// Searches a PNG file for a secret message and prints it out if found.
 #[derive(Args)]
 pub struct DecodeArgs {
     /// Path to PNG file
     png_path: PathBuf,
 
-    /// PNG chunk type
-    chunk_type: String,
+    /// PNG chunk type, only given for the chunk method
+    chunk_type: Option<String>,
+
+    /// Where the message is hidden
+    #[arg(short, long, value_enum, default_value_t)]
+    method: Method,
+
+    #[command(flatten)]
+    lsb: LsbArgs,
+
+    /// How the palette method hides the message
+    #[arg(long, value_enum, default_value_t)]
+    palette: PaletteMode,
+
+    #[command(flatten)]
+    text: TextArgs,
+
+    #[command(flatten)]
+    encryption: PassphraseArgs,
+
+    /// Entry of an ICO or CUR file to work on, the first PNG entry by default
+    #[arg(long, value_name = "N")]
+    entry: Option<usize>,
 }
 
 /// Removes a chunk from a PNG file.
@@ -58,52 +350,400 @@ pub struct RemoveArgs {
 pub struct PrintArgs {
     /// Path to PNG file
     png_path: PathBuf,
+
+    /// Entry of an ICO or CUR file to print, instead of listing the entries
+    #[arg(long, value_name = "N")]
+    entry: Option<usize>,
+}
+
+/// Checks that a PNG file is well-formed and that every chunk CRC matches.
+#[derive(Args)]
+pub struct ValidateArgs {
+    /// Path to PNG file
+    png_path: PathBuf,
+}
+
+/// Audits a PNG file for signs of hidden data.
+#[derive(Args)]
+pub struct AnalyzeArgs {
+    /// Path to PNG file
+    png_path: PathBuf,
+}
+
+/// Lists the frames of an animated PNG, extracts them, or assembles an animation.
+#[derive(Args)]
+pub struct FramesArgs {
+    /// Path to APNG file, which is written when --assemble is given
+    png_path: PathBuf,
+
+    /// Write every frame to this directory as a standalone PNG
+    #[arg(long, value_name = "DIR", conflicts_with = "assemble")]
+    extract: Option<PathBuf>,
+
+    /// Build an animation out of the PNG files in this directory, in file name order
+    #[arg(long, value_name = "DIR")]
+    assemble: Option<PathBuf>,
+
+    /// How long each assembled frame is shown, in milliseconds
+    #[arg(long, default_value_t = 100, requires = "assemble")]
+    delay: u16,
+
+    /// Number of times the assembled animation plays, where 0 means forever
+    #[arg(long, default_value_t = 0, requires = "assemble")]
+    plays: u32,
+}
+
+/// Finds PNGs inside any other file and checks each one for hidden chunks.
+#[derive(Args)]
+pub struct CarveArgs {
+    /// Path to a file that may contain PNGs, such as a document, archive or memory dump
+    path: PathBuf,
+
+    /// Write every PNG found to this directory, named after its offset
+    #[arg(short, long, value_name = "DIR")]
+    output: Option<PathBuf>,
+}
+
+/// Compares the chunks of two PNGs.
+#[derive(Args)]
+pub struct DiffArgs {
+    /// Path to the original PNG
+    old: PathBuf,
+
+    /// Path to the PNG to compare against it
+    new: PathBuf,
+
+    /// Also decode both images and report whether their pixels are identical
+    #[arg(long)]
+    pixels: bool,
+}
+
+/// Removes metadata from a PNG, leaving the image.
+#[derive(Args)]
+pub struct StripArgs {
+    /// Path to PNG file
+    png_path: PathBuf,
+
+    /// Ancillary chunk types to keep, such as sRGB,gAMA,iCCP
+    #[arg(long, value_delimiter = ',', value_name = "TYPES")]
+    keep: Vec<String>,
+
+    /// Chunk types to remove even if they would otherwise be kept, such as private critical chunks
+    #[arg(long, value_delimiter = ',', value_name = "TYPES")]
+    remove: Vec<String>,
+
+    /// Keep the ancillary chunks that are safe to copy, which don't depend on the image data
+    #[arg(long)]
+    safe_to_copy: bool,
+
+    /// Keep any data after the IEND chunk
+    #[arg(long)]
+    keep_trailing: bool,
+
+    /// List everything that was removed, and private critical chunks that were kept
+    #[arg(long)]
+    report: bool,
+
+    /// Write the stripped PNG here instead of overwriting the original
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+/// Copies ancillary and private chunks from one PNG to another.
+#[derive(Args)]
+pub struct TransplantArgs {
+    /// Path to the PNG to copy chunks from
+    source: PathBuf,
+
+    /// Path to the PNG to copy chunks into
+    target: PathBuf,
+
+    /// Chunk types to copy, every ancillary and private chunk by default
+    #[arg(long, value_delimiter = ',', value_name = "TYPES")]
+    types: Vec<String>,
+
+    /// Also copy chunks that aren't safe to copy, whose meaning depends on the image data
+    #[arg(long = "unsafe")]
+    allow_unsafe: bool,
+
+    /// Write the result here instead of overwriting the target
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+/// Reads a whole file, checking its size against `limits` before anything is read.
+fn read_file(path: &Path, limits: &Limits) -> Result<Vec<u8>> {
+    limits.check_file_size(fs::metadata(path)?.len())?;
+    Ok(fs::read(path)?)
+}
+
+/// Maps a file into memory so that large PNGs can be parsed without reading them into RAM.
+fn map_file(path: &Path, limits: &Limits) -> Result<Mmap> {
+    let file = File::open(path)?;
+    limits.check_file_size(file.metadata()?.len())?;
+
+    // SAFETY: the mapping is read-only and only lives for the duration of a single subcommand.
+    // Another process truncating the file while it is mapped is outside of our control, like it
+    // would be for any other tool that memory-maps its input.
+    Ok(unsafe { Mmap::map(&file)? })
+}
+
+/// The ICO or CUR file a PNG was taken from, and which of its entries it is.
+struct IconSource {
+    icon: Icon,
+    index: usize,
+}
+
+/// Finds the PNG to work on in a file, which is the whole file unless it is an ICO or CUR file.
+///
+/// For icon files, the directory and the chosen entry are returned too, since they are needed to
+/// write the entry back.
+fn select_png<'a>(
+    bytes: &'a [u8],
+    entry: Option<usize>,
+    limits: &Limits,
+) -> Result<(&'a [u8], Option<IconSource>)> {
+    if !ico::is_icon(bytes) {
+        if entry.is_some() {
+            return Err("--entry only applies to ICO and CUR files".into());
+        }
+        return Ok((bytes, None));
+    }
+
+    let icon = Icon::parse(bytes, limits)?;
+    let index = icon.png_entry(bytes, entry)?;
+    Ok((
+        icon.entries()[index].data(bytes),
+        Some(IconSource { icon, index }),
+    ))
 }
 
 /// Encodes a secret message into the PNG file.
-pub fn encode(args: EncodeArgs) -> Result<()> {
-    let png_bytes = fs::read(args.png_path)?;
-    let png_bytes = png_bytes.as_slice();
-    let mut png = Png::try_from(png_bytes)?;
-
-    let chunk = Chunk::new(
-        ChunkType::from_str(args.chunk_type.as_str())?,
-        args.message.as_bytes().to_vec(),
-    );
+pub fn encode(args: EncodeArgs, limits: &Limits) -> Result<()> {
+    let file_bytes = read_file(&args.png_path, limits)?;
+    let (png_bytes, icon) = select_png(&file_bytes, args.entry, limits)?;
+    let mut png = Png::parse_with_limits(png_bytes, limits)?;
+    let (chunk_type, message) = args.chunk_type_and_message()?;
+    let passphrase = args.encryption.for_method(args.method)?;
+
+    match args.method {
+        Method::Chunk => {
+            let options = SealOptions {
+                compress: args.compress,
+                passphrase: passphrase.map(str::to_string),
+            };
+            let chunk_type = ChunkType::from_str(chunk_type.unwrap_or_default())?;
+            let payload = container::seal(message.as_bytes(), &options)?;
+
+            let parts = match args.parity {
+                Some(parity_shards) => fec::encode(
+                    &payload,
+                    &FecOptions {
+                        data_shards: args.shards,
+                        parity_shards,
+                    },
+                )?,
+                None => vec![payload],
+            };
+
+            // Payload chunks left from an earlier message would be mixed up with the new parts.
+            png.retain_chunks(|c| {
+                *c.chunk_type() != chunk_type || !container::is_container(c.data())
+            });
+
+            // Anything after IEND is trailing data, so the chunks have to go before it.
+            let end = png.format().end_chunk();
+            for part in parts {
+                png.insert_chunk_before(end, Chunk::new(chunk_type, part))?;
+            }
+        }
+        Method::Lsb => {
+            let mut image = Image::decode(&png, limits)?;
+            let options = args.lsb.options()?;
+
+            println!(
+                "LSB capacity: {} bytes, message: {} bytes",
+                lsb::capacity(&image, &options)?,
+                message.len()
+            );
+
+            let report = lsb::embed(&mut image, message.as_bytes(), &options)?;
+            print_report(&report);
+
+            image.encode_into(&mut png, &EncodeOptions::default())?;
+        }
+        Method::Palette => {
+            let mut image = Image::decode(&png, limits)?;
+            let mut palette = Palette::from_png(&png)?;
+
+            match args.palette {
+                PaletteMode::Sort => {
+                    let options = args.lsb.options()?;
+                    palette::sort_by_luminance(&mut image, &mut palette)?;
+
+                    println!(
+                        "Palette index capacity: {} bytes, message: {} bytes",
+                        lsb::index_capacity(&image, &options, palette.len())?,
+                        message.len()
+                    );
+
+                    let report = lsb::embed_indices(
+                        &mut image,
+                        message.as_bytes(),
+                        &options,
+                        palette.len(),
+                    )?;
+                    print_report(&report);
+                }
+                PaletteMode::Order => {
+                    println!(
+                        "Palette order capacity: {} bytes, message: {} bytes",
+                        palette::order_capacity(&image, &palette)?,
+                        message.len()
+                    );
+
+                    palette::embed_order(&mut image, &mut palette, message.as_bytes())?;
+                }
+            }
+
+            palette.write_into(&mut png)?;
+            image.encode_into(&mut png, &EncodeOptions::default())?;
+        }
+        Method::Trailer => png.set_trailing(message.as_bytes().to_vec()),
+        Method::Text => {
+            let keyword = args.text.keyword.as_str();
+            let text = TextChunk::new(
+                args.text_chunk.into(),
+                keyword,
+                &args.text.seal(message.as_bytes(), passphrase)?,
+            )?;
+
+            // An older message under the same keyword would be found first when decoding.
+            png.retain_chunks(|chunk| {
+                !text::has_keyword(chunk.chunk_type(), chunk.data(), keyword)
+            });
+            png.insert_chunk_before(png.format().end_chunk(), text.as_chunk()?)?;
+        }
+        Method::Slack => slack::embed(&mut png, message.as_bytes(), limits)?,
+        Method::Frames => {
+            let options = args.lsb.options()?;
+
+            println!(
+                "Frame LSB capacity: {} bytes, message: {} bytes",
+                frame_lsb::capacity(&png, &options, limits)?,
+                message.len()
+            );
 
-    png.append_chunk(chunk);
+            let report = frame_lsb::embed(&mut png, message.as_bytes(), &options, limits)?;
+            print_report(&report);
+        }
+    }
 
     if let Some(out_path) = args.output.as_deref() {
-        fs::write(out_path, png.as_bytes())?;
+        let bytes = match &icon {
+            Some(IconSource { icon, index }) => {
+                icon.replace_entry(&file_bytes, *index, &png.as_bytes())?
+            }
+            None => png.as_bytes(),
+        };
+        fs::write(out_path, bytes)?;
     }
 
     Ok(())
 }
 
+fn print_report(report: &EmbedReport) {
+    match report.efficiency() {
+        Some(efficiency) => println!(
+            "Modified {} of {} samples, embedding efficiency: {efficiency:.2} bits per change",
+            report.modified, report.samples
+        ),
+        None => println!(
+            "Modified none of {} samples, the message was already there",
+            report.samples
+        ),
+    }
+}
+
 /// Searches a PNG file for a secret message and prints it out if found.
-/// Encodes a secret message into the PNG file.
-pub fn decode(args: DecodeArgs) -> Result<()> {
-    let png_bytes = fs::read(args.png_path)?;
-    let png_bytes = png_bytes.as_slice();
-    let png = Png::try_from(png_bytes)?;
+pub fn decode(args: DecodeArgs, limits: &Limits) -> Result<()> {
+    let file_bytes = map_file(&args.png_path, limits)?;
+    let (png_bytes, _) = select_png(&file_bytes, args.entry, limits)?;
+    // Parse errors are only reported by methods that need the parsed file, since split payloads
+    // can be recovered from damaged files.
+    let png = PngRef::parse_with_limits(png_bytes, limits);
+    let passphrase = args.encryption.for_method(args.method)?;
 
-    let message = png
-        .chunks()
-        .iter()
-        .find(|c| c.chunk_type().to_string() == args.chunk_type)
-        .map(|c| c.chunk_type().to_string())
-        .ok_or("could not find a chunk with a matching message")?;
+    let message = match args.method {
+        Method::Chunk => {
+            let chunk_type = args
+                .chunk_type
+                .as_deref()
+                .ok_or("the chunk method needs a chunk type")?;
+
+            let parts = fec::find_parts(png_bytes, chunk_type);
+            let payload = if parts.is_empty() {
+                // Other chunks of the same type that don't hold our payload are skipped.
+                png?.chunks()
+                    .find(|c| {
+                        c.chunk_type().bytes() == chunk_type.as_bytes()
+                            && container::is_container(c.data())
+                    })
+                    .ok_or("could not find a chunk with a matching message")?
+                    .data()
+                    .to_vec()
+            } else {
+                fec::decode(parts, limits)?
+            };
 
-    println!("{message}");
+            container::open(&payload, passphrase, limits)?
+        }
+        Method::Lsb => {
+            let image = Image::decode(&Png::from(png?), limits)?;
+            lsb::extract(&image, &args.lsb.options()?)?
+        }
+        Method::Palette => {
+            let png = Png::from(png?);
+            let image = Image::decode(&png, limits)?;
+
+            match args.palette {
+                PaletteMode::Sort => lsb::extract_indices(
+                    &image,
+                    &args.lsb.options()?,
+                    Palette::from_png(&png)?.len(),
+                )?,
+                PaletteMode::Order => palette::extract_order(&image, &Palette::from_png(&png)?)?,
+            }
+        }
+        Method::Trailer => {
+            let png = png?;
+            match png.trailing() {
+                [] => {
+                    let end = png.format().end_chunk();
+                    return Err(format!("there is no data after the {end} chunk").into());
+                }
+                trailing => trailing.to_vec(),
+            }
+        }
+        Method::Text => {
+            let text = text::find(&png?, &args.text.keyword, limits)?
+                .ok_or("could not find a text chunk with a matching keyword")?;
+
+            args.text.open(text.text(), passphrase)?
+        }
+        Method::Slack => slack::extract(&Png::from(png?), limits)?,
+        Method::Frames => frame_lsb::extract(&Png::from(png?), &args.lsb.options()?, limits)?,
+    };
+
+    println!("{}", String::from_utf8(message)?);
 
     Ok(())
 }
 
 /// Removes a chunk from a PNG file.
-pub fn remove(args: RemoveArgs) -> Result<()> {
-    let png_bytes = fs::read(args.png_path)?;
-    let png_bytes = png_bytes.as_slice();
-    let mut png = Png::try_from(png_bytes)?;
+pub fn remove(args: RemoveArgs, limits: &Limits) -> Result<()> {
+    let png_bytes = read_file(&args.png_path, limits)?;
+    let mut png = Png::parse_with_limits(&png_bytes, limits)?;
 
     let chunk_type_to_remove = ChunkType::from_str(args.chunk_type.as_str())?;
 
@@ -118,14 +758,289 @@ pub fn remove(args: RemoveArgs) -> Result<()> {
 }
 
 /// Print out all of the chunks in a PNG file.
-pub fn print(args: PrintArgs) -> Result<()> {
-    let png_bytes = fs::read(args.png_path)?;
-    let png_bytes = png_bytes.as_slice();
-    let png = Png::try_from(png_bytes)?;
+pub fn print(args: PrintArgs, limits: &Limits) -> Result<()> {
+    let file_bytes = map_file(&args.png_path, limits)?;
+
+    if ico::is_icon(&file_bytes) && args.entry.is_none() {
+        let icon = Icon::parse(&file_bytes, limits)?;
+        println!("{} file with {} entries", icon.kind(), icon.entries().len());
+
+        for (i, entry) in icon.entries().iter().enumerate() {
+            println!(
+                "Entry {i}: {}x{}, {}, {} bytes",
+                entry.width(),
+                entry.height(),
+                if entry.is_png(&file_bytes) {
+                    "PNG"
+                } else {
+                    "BMP"
+                },
+                entry.data(&file_bytes).len()
+            );
+        }
+
+        return Ok(());
+    }
+
+    let (png_bytes, _) = select_png(&file_bytes, args.entry, limits)?;
+    let png = PngRef::parse_with_limits(png_bytes, limits)?;
 
     for chunk in png.chunks() {
         println!("{chunk}");
     }
 
+    if !png.trailing().is_empty() {
+        println!("Trailing data: {} bytes", png.trailing().len());
+    }
+
+    Ok(())
+}
+
+/// Checks that a PNG file is well-formed and that every chunk CRC matches.
+pub fn validate(args: ValidateArgs, limits: &Limits) -> Result<()> {
+    let png_bytes = map_file(&args.png_path, limits)?;
+    let png = PngRef::parse_with_limits(&png_bytes, limits)?;
+
+    // Animated PNGs also need their frame chunks in order.
+    if png.chunk_by_type("acTL").is_some() {
+        let animation = Animation::from_png(&Png::from(png))?;
+        println!(
+            "{}: valid, {} chunks, {} frames",
+            args.png_path.display(),
+            png.chunks().count(),
+            animation.frames.len()
+        );
+        return Ok(());
+    }
+
+    println!(
+        "{}: valid, {} chunks",
+        args.png_path.display(),
+        png.chunks().count()
+    );
+
+    Ok(())
+}
+
+/// Audits a PNG file for signs of hidden data.
+pub fn analyze(args: AnalyzeArgs, limits: &Limits) -> Result<()> {
+    let png_bytes = map_file(&args.png_path, limits)?;
+    let png = PngRef::parse_with_limits(&png_bytes, limits)?;
+
+    let findings = analyze::inspect(&png);
+    if findings.is_empty() {
+        println!("No structural anomalies found");
+    }
+    for finding in findings {
+        println!("Suspicious: {finding}");
+    }
+
+    let detections =
+        Image::decode(&Png::from(png), limits).and_then(|image| steganalysis::detect(&image));
+    match detections {
+        Ok(detections) => {
+            for detection in detections {
+                println!("LSB {detection}");
+            }
+        }
+        Err(err) => println!("Skipped statistical tests: {err}"),
+    }
+
+    Ok(())
+}
+
+/// Lists the frames of an animated PNG, extracts them, or assembles an animation.
+pub fn frames(args: FramesArgs, limits: &Limits) -> Result<()> {
+    if let Some(dir) = args.assemble.as_deref() {
+        let mut paths = fs::read_dir(dir)?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<Result<Vec<_>>>()?;
+        paths.retain(|path| {
+            path.extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+        });
+        paths.sort();
+
+        let pngs = paths
+            .iter()
+            .map(|path| Png::parse_with_limits(&read_file(path, limits)?, limits))
+            .collect::<Result<Vec<_>>>()?;
+        let apng = apng::assemble(&pngs, args.delay, args.plays)?;
+        fs::write(&args.png_path, apng.as_bytes())?;
+
+        println!(
+            "Assembled {} frames into {}",
+            pngs.len(),
+            args.png_path.display()
+        );
+        return Ok(());
+    }
+
+    let png_bytes = map_file(&args.png_path, limits)?;
+    let png = Png::from(PngRef::parse_with_limits(&png_bytes, limits)?);
+    let animation = Animation::from_png(&png)?;
+
+    match animation.control.num_plays {
+        0 => println!("{} frames, playing forever", animation.frames.len()),
+        plays => println!("{} frames, playing {plays} times", animation.frames.len()),
+    }
+    if !animation.default_is_frame {
+        println!("The default image is not part of the animation");
+    }
+
+    for (i, frame) in animation.frames.iter().enumerate() {
+        println!("Frame {i}: {}", frame.control);
+    }
+
+    if let Some(dir) = args.extract.as_deref() {
+        fs::create_dir_all(dir)?;
+        for i in 0..animation.frames.len() {
+            let path = dir.join(format!("frame_{i:03}.png"));
+            fs::write(&path, animation.extract(&png, i)?.as_bytes())?;
+            println!("Wrote {}", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Finds PNGs inside any other file and checks each one for hidden chunks.
+pub fn carve(args: CarveArgs, limits: &Limits) -> Result<()> {
+    let bytes = map_file(&args.path, limits)?;
+    let found = carve::carve(&bytes, limits);
+
+    if found.is_empty() {
+        println!("No complete PNGs found");
+        return Ok(());
+    }
+    if let Some(dir) = args.output.as_deref() {
+        fs::create_dir_all(dir)?;
+    }
+
+    for carved in found {
+        println!(
+            "PNG at offset {} ({:#x}): {} bytes, {} chunks",
+            carved.offset,
+            carved.offset,
+            carved.bytes.len(),
+            carved.png.chunks().count()
+        );
+
+        // Offsets in the findings are relative to the start of the carved PNG.
+        for finding in analyze::inspect(&carved.png) {
+            println!("  Suspicious: {finding}");
+        }
+
+        if let Some(dir) = args.output.as_deref() {
+            let path = dir.join(format!("carved_{:08x}.png", carved.offset));
+            fs::write(&path, carved.bytes)?;
+            println!("  Wrote {}", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Compares the chunks of two PNGs, and optionally their pixels.
+pub fn diff(args: DiffArgs, limits: &Limits) -> Result<()> {
+    let old = Png::parse_with_limits(&read_file(&args.old, limits)?, limits)?;
+    let new = Png::parse_with_limits(&read_file(&args.new, limits)?, limits)?;
+
+    let changes = diff::diff(&old, &new, limits);
+    if changes.is_empty() {
+        println!("Chunks and trailing data: identical");
+    }
+    for change in changes {
+        println!("{change}");
+    }
+
+    if args.pixels {
+        let (old, new) = (Image::decode(&old, limits)?, Image::decode(&new, limits)?);
+        println!("Pixels: {}", diff::compare_pixels(&old, &new));
+    }
+
+    Ok(())
+}
+
+/// Removes metadata from a PNG, leaving the image.
+pub fn strip(args: StripArgs, limits: &Limits) -> Result<()> {
+    let mut png = Png::parse_with_limits(&read_file(&args.png_path, limits)?, limits)?;
+    let parse_types = |types: &[String]| -> Result<Vec<ChunkType>> {
+        types.iter().map(|t| ChunkType::from_str(t)).collect()
+    };
+    let options = StripOptions {
+        keep: parse_types(&args.keep)?,
+        remove: parse_types(&args.remove)?,
+        safe_to_copy: args.safe_to_copy,
+        keep_trailing: args.keep_trailing,
+    };
+
+    let report = strip::strip(&mut png, &options)?;
+    fs::write(
+        args.output.as_ref().unwrap_or(&args.png_path),
+        png.as_bytes(),
+    )?;
+
+    if args.report {
+        for chunk in &report.removed {
+            println!(
+                "Removed {}, {} bytes",
+                chunk.chunk_type(),
+                chunk.data().len()
+            );
+        }
+        if report.trailing > 0 {
+            println!("Removed {} bytes after the end chunk", report.trailing);
+        }
+        for chunk_type in &report.kept_private {
+            println!(
+                "Kept {chunk_type}, a private critical chunk that could hide data, remove it with --remove {chunk_type}"
+            );
+        }
+
+        let chunk_bytes: usize = report.removed.iter().map(|c| c.as_bytes().len()).sum();
+        println!(
+            "Removed {} chunks, the file is {} bytes smaller",
+            report.removed.len(),
+            chunk_bytes + report.trailing
+        );
+    }
+
+    Ok(())
+}
+
+/// Copies ancillary and private chunks from one PNG to another.
+pub fn transplant(args: TransplantArgs, limits: &Limits) -> Result<()> {
+    let source = Png::parse_with_limits(&read_file(&args.source, limits)?, limits)?;
+    let mut target = Png::parse_with_limits(&read_file(&args.target, limits)?, limits)?;
+    let options = TransplantOptions {
+        types: args
+            .types
+            .iter()
+            .map(|t| ChunkType::from_str(t))
+            .collect::<Result<_>>()?,
+        allow_unsafe: args.allow_unsafe,
+    };
+
+    let report = transplant::transplant(&source, &mut target, &options)?;
+    fs::write(
+        args.output.as_ref().unwrap_or(&args.target),
+        target.as_bytes(),
+    )?;
+
+    for chunk in &report.copied {
+        println!(
+            "Copied {}, {} bytes",
+            chunk.chunk_type(),
+            chunk.data().len()
+        );
+    }
+    for chunk_type in &report.copied_unsafe {
+        eprintln!("Warning: copied {chunk_type} although it isn't safe to copy, so it may not match the image");
+    }
+    for (chunk_type, reason) in &report.skipped {
+        println!("Skipped {chunk_type}: {reason}");
+    }
+
     Ok(())
 }
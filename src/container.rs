@@ -0,0 +1,272 @@
+use crate::{cipher, png::Limits, zlib};
+use sha2::{Digest, Sha256};
+
+/// Marks the start of every payload written by secretpng.
+pub const MAGIC: &[u8; 4] = b"sPNG";
+
+/// The newest container version this build reads and writes.
+pub const VERSION: u8 = 1;
+
+const DIGEST_LEN: usize = 32;
+
+/// Magic, version, flags, original length and digest.
+pub const HEADER_LEN: usize = MAGIC.len() + 1 + 1 + 4 + DIGEST_LEN;
+
+/// What was done to the message before it was stored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Flags(u8);
+
+impl Flags {
+    /// The message was compressed with zlib.
+    pub const COMPRESSED: Flags = Flags(1);
+    /// The (possibly compressed) message was encrypted with `cipher::encrypt`.
+    pub const ENCRYPTED: Flags = Flags(1 << 1);
+    /// The stored bytes are one part of a payload split over several carriers.
+    pub const CHUNKED: Flags = Flags(1 << 2);
+
+    const KNOWN: u8 = Self::COMPRESSED.0 | Self::ENCRYPTED.0 | Self::CHUNKED.0;
+
+    pub fn contains(self, other: Flags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: Flags) {
+        self.0 |= other.0;
+    }
+
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+}
+
+/// The fixed-size header in front of every payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub version: u8,
+    pub flags: Flags,
+    /// Length of the message before compression and encryption.
+    pub length: u32,
+    /// SHA-256 of the stored body, so corruption is caught before decrypting or inflating it.
+    pub digest: [u8; DIGEST_LEN],
+}
+
+impl Header {
+    /// Parses the header at the start of `data`, returning it with the body that follows.
+    ///
+    /// Versions newer than `VERSION` and unknown flags are refused, since their body can't be
+    /// interpreted safely.
+    pub fn parse(data: &[u8]) -> crate::Result<(Self, &[u8])> {
+        if !is_container(data) {
+            return Err("data is not a secretpng payload".into());
+        }
+        if data.len() < HEADER_LEN {
+            return Err("payload header is truncated".into());
+        }
+
+        let (header, body) = data.split_at(HEADER_LEN);
+        let version = header[4];
+        if version == 0 || version > VERSION {
+            return Err(format!(
+                "payload uses container version {version}, but only versions up to {VERSION} are supported"
+            )
+            .into());
+        }
+
+        let flags = header[5];
+        if flags & !Flags::KNOWN != 0 {
+            return Err(format!("payload has unknown flags {flags:#010b}").into());
+        }
+
+        Ok((
+            Self {
+                version,
+                flags: Flags(flags),
+                length: u32::from_be_bytes(header[6..10].try_into()?),
+                digest: header[10..].try_into()?,
+            },
+            body,
+        ))
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        [
+            &MAGIC[..],
+            &[self.version, self.flags.bits()],
+            &self.length.to_be_bytes(),
+            &self.digest,
+        ]
+        .concat()
+    }
+}
+
+/// How `seal` transforms a message before storing it.
+#[derive(Debug, Clone, Default)]
+pub struct SealOptions {
+    pub compress: bool,
+    pub passphrase: Option<String>,
+}
+
+/// Whether `data` starts with the container magic.
+pub fn is_container(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Wraps `message` in a container, compressing and encrypting it as requested.
+pub fn seal(message: &[u8], options: &SealOptions) -> crate::Result<Vec<u8>> {
+    let length = u32::try_from(message.len()).map_err(|_| "message is too long")?;
+    let mut flags = Flags::default();
+    let mut body = message.to_vec();
+
+    if options.compress {
+        body = zlib::deflate(&body, zlib::DEFAULT_LEVEL);
+        flags.insert(Flags::COMPRESSED);
+    }
+    if let Some(passphrase) = &options.passphrase {
+        body = cipher::encrypt(passphrase, &body)?;
+        flags.insert(Flags::ENCRYPTED);
+    }
+
+    let header = Header {
+        version: VERSION,
+        flags,
+        length,
+        digest: Sha256::digest(&body).into(),
+    };
+
+    Ok([header.as_bytes(), body].concat())
+}
+
+/// Wraps one part of a payload that is split over several carriers.
+pub fn seal_part(part: &[u8]) -> crate::Result<Vec<u8>> {
+    let header = Header {
+        version: VERSION,
+        flags: Flags::CHUNKED,
+        length: u32::try_from(part.len()).map_err(|_| "payload part is too long")?,
+        digest: Sha256::digest(part).into(),
+    };
+
+    Ok([header.as_bytes(), part.to_vec()].concat())
+}
+
+/// Validates a container made by `seal_part`, returning the part inside it.
+pub fn open_part(data: &[u8]) -> crate::Result<&[u8]> {
+    let (header, body) = verify(data)?;
+    if !header.flags.contains(Flags::CHUNKED) || body.len() != header.length as usize {
+        return Err("payload is not part of a split message".into());
+    }
+
+    Ok(body)
+}
+
+/// Parses the header and checks the digest of the body that follows it.
+fn verify(data: &[u8]) -> crate::Result<(Header, &[u8])> {
+    let (header, body) = Header::parse(data)?;
+    if <[u8; DIGEST_LEN]>::from(Sha256::digest(body)) != header.digest {
+        return Err("payload digest does not match, the data is corrupted".into());
+    }
+
+    Ok((header, body))
+}
+
+/// Validates a container and recovers the message inside it.
+pub fn open(data: &[u8], passphrase: Option<&str>, limits: &Limits) -> crate::Result<Vec<u8>> {
+    let (header, body) = verify(data)?;
+
+    if header.flags.contains(Flags::CHUNKED) {
+        return Err("payload is only one part of a split message".into());
+    }
+
+    let mut message = body.to_vec();
+    if header.flags.contains(Flags::ENCRYPTED) {
+        let passphrase = passphrase.ok_or("payload is encrypted, a passphrase is needed")?;
+        message = cipher::decrypt(passphrase, &message)?;
+    }
+    if header.flags.contains(Flags::COMPRESSED) {
+        limits.check_decompressed_size(header.length as usize)?;
+        message = zlib::inflate(&message, limits)?;
+    }
+
+    if message.len() != header.length as usize {
+        return Err("payload length does not match its header".into());
+    }
+
+    Ok(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_open() {
+        let message = "dice ".repeat(50);
+        let options = SealOptions {
+            compress: true,
+            passphrase: None,
+        };
+        let sealed = seal(message.as_bytes(), &options).unwrap();
+
+        assert!(sealed.len() < HEADER_LEN + message.len());
+        assert_eq!(
+            open(&sealed, None, &Limits::default()).unwrap(),
+            message.as_bytes()
+        );
+
+        let (header, _) = Header::parse(&sealed).unwrap();
+        assert_eq!(header.flags, Flags::COMPRESSED);
+        assert_eq!(header.length, 250);
+    }
+
+    #[test]
+    fn test_seal_open_encrypted() {
+        let options = SealOptions {
+            compress: true,
+            passphrase: Some("passphrase".to_string()),
+        };
+        let sealed = seal(b"attack at dawn", &options).unwrap();
+
+        assert_eq!(
+            open(&sealed, Some("passphrase"), &Limits::default()).unwrap(),
+            b"attack at dawn"
+        );
+        assert!(open(&sealed, None, &Limits::default()).is_err());
+        assert!(open(&sealed, Some("wrong"), &Limits::default()).is_err());
+    }
+
+    #[test]
+    fn test_corruption_detected() {
+        let mut sealed = seal(b"attack at dawn", &SealOptions::default()).unwrap();
+        *sealed.last_mut().unwrap() ^= 1;
+
+        let err = open(&sealed, None, &Limits::default()).unwrap_err();
+        assert!(err.to_string().contains("digest"));
+        assert!(open(&sealed[..HEADER_LEN - 1], None, &Limits::default()).is_err());
+    }
+
+    #[test]
+    fn test_seal_open_part() {
+        let sealed = seal_part(b"one of several").unwrap();
+
+        assert_eq!(open_part(&sealed).unwrap(), b"one of several");
+        assert!(open(&sealed, None, &Limits::default()).is_err());
+
+        let whole = seal(b"one of several", &SealOptions::default()).unwrap();
+        assert!(open_part(&whole).is_err());
+    }
+
+    #[test]
+    fn test_unknown_version_and_flags() {
+        let sealed = seal(b"attack at dawn", &SealOptions::default()).unwrap();
+
+        let mut future = sealed.clone();
+        future[4] = VERSION + 1;
+        let err = open(&future, None, &Limits::default()).unwrap_err();
+        assert!(err.to_string().contains("version 2"));
+
+        let mut flagged = sealed.clone();
+        flagged[5] = 0x80;
+        assert!(open(&flagged, None, &Limits::default()).is_err());
+
+        assert!(open(b"attack at dawn", None, &Limits::default()).is_err());
+    }
+}
@@ -0,0 +1,477 @@
+use crate::{
+    image::Image,
+    png::{Chunk, ChunkType, Limits, Png},
+    text::{TextChunk, TextKind},
+};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+};
+
+/// The part of two byte strings that differs, after their common prefix and suffix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ByteDiff {
+    pub offset: usize,
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+}
+
+/// Compares two byte strings, returning `None` if they are equal.
+pub fn byte_diff(old: &[u8], new: &[u8]) -> Option<ByteDiff> {
+    if old == new {
+        return None;
+    }
+
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    Some(ByteDiff {
+        offset: prefix,
+        old: old[prefix..old.len() - suffix].to_vec(),
+        new: new[prefix..new.len() - suffix].to_vec(),
+    })
+}
+
+/// One difference between the chunk lists of two PNGs. Indices count chunks from the start of
+/// each file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Removed {
+        chunk: String,
+        index: usize,
+        length: usize,
+    },
+    Added {
+        chunk: String,
+        index: usize,
+        length: usize,
+    },
+    /// A chunk whose position changed relative to the chunks around it.
+    Moved {
+        chunk: String,
+        from: usize,
+        to: usize,
+    },
+    /// A chunk whose data changed, with the change to the text for text chunks.
+    Modified {
+        chunk: String,
+        from: usize,
+        to: usize,
+        old_length: usize,
+        new_length: usize,
+        text: Option<ByteDiff>,
+    },
+    /// The bytes after the end chunk changed, starting at `offset` into them.
+    Trailing {
+        old_length: usize,
+        new_length: usize,
+        offset: usize,
+    },
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Removed {
+                chunk,
+                index,
+                length,
+            } => write!(f, "- {chunk} at {index}, {length} bytes"),
+            Change::Added {
+                chunk,
+                index,
+                length,
+            } => write!(f, "+ {chunk} at {index}, {length} bytes"),
+            Change::Moved { chunk, from, to } => write!(f, "> {chunk} moved from {from} to {to}"),
+            Change::Modified {
+                chunk,
+                from,
+                to,
+                old_length,
+                new_length,
+                text,
+            } => {
+                write!(
+                    f,
+                    "~ {chunk} at {from} -> {to}, {old_length} -> {new_length} bytes"
+                )?;
+                if let Some(diff) = text {
+                    write!(
+                        f,
+                        "\n    at byte {}: \"{}\" -> \"{}\"",
+                        diff.offset,
+                        diff.old.escape_ascii(),
+                        diff.new.escape_ascii()
+                    )?;
+                }
+                Ok(())
+            }
+            Change::Trailing {
+                old_length,
+                new_length,
+                offset,
+            } => write!(
+                f,
+                "~ trailing data, {old_length} -> {new_length} bytes, differing from byte {offset}"
+            ),
+        }
+    }
+}
+
+/// What pairs up a chunk in one file with a chunk in the other: its type, its keyword if it is a
+/// text chunk, and how many chunks with the same type and keyword come before it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    chunk_type: ChunkType,
+    keyword: Option<Vec<u8>>,
+    occurrence: usize,
+}
+
+impl Key {
+    fn name(&self) -> String {
+        match &self.keyword {
+            Some(keyword) => format!("{} \"{}\"", self.chunk_type, keyword.escape_ascii()),
+            None => self.chunk_type.to_string(),
+        }
+    }
+}
+
+fn keys(png: &Png) -> Vec<Key> {
+    let mut seen: HashMap<(ChunkType, Option<Vec<u8>>), usize> = HashMap::new();
+
+    png.chunks()
+        .iter()
+        .map(|chunk| {
+            let chunk_type = *chunk.chunk_type();
+            let keyword = TextKind::from_chunk_type(&chunk_type).map(|_| {
+                chunk
+                    .data()
+                    .split(|&b| b == 0)
+                    .next()
+                    .unwrap_or_default()
+                    .to_vec()
+            });
+
+            let count = seen.entry((chunk_type, keyword.clone())).or_default();
+            *count += 1;
+
+            Key {
+                chunk_type,
+                keyword,
+                occurrence: *count - 1,
+            }
+        })
+        .collect()
+}
+
+/// The bytes compared for a text chunk: its decoded text if possible, or else its raw data.
+fn text_bytes(chunk: &Chunk, limits: &Limits) -> Vec<u8> {
+    match TextChunk::parse(chunk.chunk_type(), chunk.data(), limits) {
+        Ok(text) => text.text().as_bytes().to_vec(),
+        Err(_) => chunk.data().to_vec(),
+    }
+}
+
+/// Positions in `sequence` that make up a longest strictly increasing subsequence of it.
+fn longest_increasing(sequence: &[usize]) -> Vec<usize> {
+    // tails[k] is the position ending the smallest-valued increasing run of length k + 1.
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous = vec![None; sequence.len()];
+
+    for (i, &value) in sequence.iter().enumerate() {
+        let k = tails.partition_point(|&t| sequence[t] < value);
+        previous[i] = k.checked_sub(1).map(|k| tails[k]);
+        if k == tails.len() {
+            tails.push(i);
+        } else {
+            tails[k] = i;
+        }
+    }
+
+    let mut positions = Vec::with_capacity(tails.len());
+    let mut next = tails.last().copied();
+    while let Some(i) = next {
+        positions.push(i);
+        next = previous[i];
+    }
+    positions.reverse();
+    positions
+}
+
+/// Aligns the chunks of `old` and `new` and lists what changed between them.
+///
+/// Chunks are paired up by type, by keyword for text chunks, and by their order among chunks of
+/// the same kind. Unpaired chunks were removed or added. Paired chunks are reported as moved when
+/// they are out of order with the largest set of paired chunks that kept their order. Bytes after
+/// the end chunk are compared too, as they are a place to hide data in their own right.
+pub fn diff(old: &Png, new: &Png, limits: &Limits) -> Vec<Change> {
+    let (old_keys, new_keys) = (keys(old), keys(new));
+    let new_positions: HashMap<&Key, usize> = new_keys
+        .iter()
+        .enumerate()
+        .map(|(i, key)| (key, i))
+        .collect();
+    let old_positions: HashMap<&Key, usize> = old_keys
+        .iter()
+        .enumerate()
+        .map(|(i, key)| (key, i))
+        .collect();
+
+    let mut changes = Vec::new();
+    let mut pairs = Vec::new();
+    for (from, key) in old_keys.iter().enumerate() {
+        match new_positions.get(key) {
+            Some(&to) => pairs.push((from, to)),
+            None => changes.push(Change::Removed {
+                chunk: key.name(),
+                index: from,
+                length: old.chunks()[from].data().len(),
+            }),
+        }
+    }
+
+    let targets: Vec<usize> = pairs.iter().map(|&(_, to)| to).collect();
+    let mut in_order = vec![false; pairs.len()];
+    for i in longest_increasing(&targets) {
+        in_order[i] = true;
+    }
+
+    for ((from, to), in_order) in pairs.into_iter().zip(in_order) {
+        let (old_chunk, new_chunk) = (&old.chunks()[from], &new.chunks()[to]);
+        let chunk = old_keys[from].name();
+
+        if !in_order {
+            changes.push(Change::Moved {
+                chunk: chunk.clone(),
+                from,
+                to,
+            });
+        }
+
+        if old_chunk.data() != new_chunk.data() {
+            let text = TextKind::from_chunk_type(old_chunk.chunk_type()).and_then(|_| {
+                byte_diff(
+                    &text_bytes(old_chunk, limits),
+                    &text_bytes(new_chunk, limits),
+                )
+            });
+
+            changes.push(Change::Modified {
+                chunk,
+                from,
+                to,
+                old_length: old_chunk.data().len(),
+                new_length: new_chunk.data().len(),
+                text,
+            });
+        }
+    }
+
+    for (index, key) in new_keys.iter().enumerate() {
+        if !old_positions.contains_key(key) {
+            changes.push(Change::Added {
+                chunk: key.name(),
+                index,
+                length: new.chunks()[index].data().len(),
+            });
+        }
+    }
+
+    if let Some(trailing) = byte_diff(old.trailing(), new.trailing()) {
+        changes.push(Change::Trailing {
+            old_length: old.trailing().len(),
+            new_length: new.trailing().len(),
+            offset: trailing.offset,
+        });
+    }
+
+    changes
+}
+
+/// How the decoded pixels of two images compare.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelDiff {
+    Identical,
+    /// The images differ in size, bit depth or color type, so their pixels can't be compared.
+    Incomparable,
+    /// Number of bytes of decoded pixel data that differ.
+    Different {
+        bytes: usize,
+        total: usize,
+    },
+}
+
+impl Display for PixelDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PixelDiff::Identical => write!(f, "identical"),
+            PixelDiff::Incomparable => write!(f, "different size or pixel format"),
+            PixelDiff::Different { bytes, total } => write!(f, "{bytes} of {total} bytes differ"),
+        }
+    }
+}
+
+/// Compares the decoded pixels of two images, ignoring how they were filtered, compressed or
+/// interlaced. Indexed-color images compare their palette indices.
+pub fn compare_pixels(old: &Image, new: &Image) -> PixelDiff {
+    let layout = |image: &Image| {
+        let ihdr = image.ihdr();
+        (ihdr.width, ihdr.height, ihdr.bit_depth, ihdr.color_type)
+    };
+    if layout(old) != layout(new) {
+        return PixelDiff::Incomparable;
+    }
+
+    let bytes = old
+        .pixels()
+        .iter()
+        .zip(new.pixels())
+        .filter(|(a, b)| a != b)
+        .count();
+
+    match bytes {
+        0 => PixelDiff::Identical,
+        bytes => PixelDiff::Different {
+            bytes,
+            total: old.pixels().len(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{image::EncodeOptions, png::tests::PNG_FILE, text::TextKind};
+
+    fn testing_png() -> Png {
+        Png::try_from(&PNG_FILE[..]).unwrap()
+    }
+
+    fn text(keyword: &str, text: &str) -> Chunk {
+        TextChunk::new(TextKind::Compressed, keyword, text)
+            .unwrap()
+            .as_chunk()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_byte_diff() {
+        assert_eq!(byte_diff(b"same", b"same"), None);
+        assert_eq!(
+            byte_diff(b"taken in Paris", b"taken in Zurich"),
+            Some(ByteDiff {
+                offset: 9,
+                old: b"Paris".to_vec(),
+                new: b"Zurich".to_vec(),
+            })
+        );
+        assert_eq!(
+            byte_diff(b"abc", b"abXbc"),
+            Some(ByteDiff {
+                offset: 2,
+                old: vec![],
+                new: b"Xb".to_vec(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_longest_increasing() {
+        assert_eq!(longest_increasing(&[0, 1, 2]), [0, 1, 2]);
+        assert_eq!(longest_increasing(&[2, 0, 1, 3]), [1, 2, 3]);
+        assert!(longest_increasing(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_diff() {
+        let mut old = testing_png();
+        old.insert_chunk_before("IEND", text("Comment", "taken in Paris"))
+            .unwrap();
+
+        // Swap gAMA and sRGB, remove RuSt, edit the comment and add a title.
+        let mut chunks = old.chunks().to_vec();
+        chunks.swap(1, 2);
+        let mut new = Png::from_chunks(chunks);
+        new.remove_chunk("RuSt").unwrap();
+        new.retain_chunks(|c| c.chunk_type().bytes() != *b"zTXt");
+        new.insert_chunk_before("IEND", text("Comment", "taken in Zurich"))
+            .unwrap();
+        new.insert_chunk_before("IEND", text("Title", "Dice"))
+            .unwrap();
+
+        let changes = diff(&old, &new, &Limits::default());
+        let lines: Vec<String> = changes.iter().map(Change::to_string).collect();
+
+        assert_eq!(
+            lines,
+            [
+                "- RuSt at 5, 3 bytes",
+                "> sRGB moved from 1 to 2",
+                "~ zTXt \"Comment\" at 6 -> 5, 31 -> 32 bytes\n    at byte 9: \"Paris\" -> \"Zurich\"",
+                "+ zTXt \"Title\" at 6, 19 bytes",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trailing() {
+        let old = testing_png();
+        let mut new = testing_png();
+        new.set_trailing(b"SECRET".to_vec());
+
+        assert_eq!(
+            diff(&old, &new, &Limits::default()),
+            [Change::Trailing {
+                old_length: 0,
+                new_length: 6,
+                offset: 0,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_identical() {
+        assert!(diff(&testing_png(), &testing_png(), &Limits::default()).is_empty());
+    }
+
+    #[test]
+    fn test_compare_pixels() {
+        let png = testing_png();
+        let image = Image::decode(&png, &Limits::default()).unwrap();
+
+        // Recompressing with different settings keeps the pixels.
+        let mut recompressed = testing_png();
+        let options = EncodeOptions {
+            compression_level: 1,
+            idat_size: 100,
+            ..EncodeOptions::default()
+        };
+        image.encode_into(&mut recompressed, &options).unwrap();
+        assert_ne!(
+            diff(&png, &recompressed, &Limits::default()),
+            Vec::<Change>::new()
+        );
+        assert_eq!(
+            compare_pixels(
+                &image,
+                &Image::decode(&recompressed, &Limits::default()).unwrap()
+            ),
+            PixelDiff::Identical
+        );
+
+        let mut changed = image.clone();
+        changed.pixels_mut()[0] ^= 1;
+        assert_eq!(
+            compare_pixels(&image, &changed),
+            PixelDiff::Different {
+                bytes: 1,
+                total: image.pixels().len()
+            }
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use crate::{container, limits::Limits};
+use reed_solomon_erasure::galois_8::ReedSolomon;
+
+/// Index, number of data shards, number of parity shards and payload length.
+const SHARD_HEADER_LEN: usize = 1 + 1 + 1 + 4;
+
+/// How a payload is split up and how much redundancy is added to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FecOptions {
+    /// Number of shards the payload is split into.
+    pub data_shards: u8,
+    /// Number of extra shards, which is how many shards can be lost before the payload is.
+    pub parity_shards: u8,
+}
+
+/// Splits `payload` into Reed–Solomon coded shards, each wrapped with `container::seal_part`.
+///
+/// Any `data_shards` of the returned parts are enough to recover the payload, and every part
+/// carries its own digest, so damaged parts are recognised and treated as missing.
+pub fn encode(payload: &[u8], options: &FecOptions) -> crate::Result<Vec<Vec<u8>>> {
+    let data_shards = usize::from(options.data_shards);
+    let parity_shards = usize::from(options.parity_shards);
+    let codec = ReedSolomon::new(data_shards, parity_shards)?;
+    let length = u32::try_from(payload.len()).map_err(|_| "payload is too long")?;
+
+    let shard_len = payload.len().div_ceil(data_shards).max(1);
+    let mut shards: Vec<Vec<u8>> = (0..data_shards + parity_shards)
+        .map(|i| {
+            let start = (i * shard_len).min(payload.len());
+            let end = (start + shard_len).min(payload.len());
+            let mut shard = payload[start..end].to_vec();
+            shard.resize(shard_len, 0);
+            shard
+        })
+        .collect();
+    codec.encode(&mut shards)?;
+
+    shards
+        .iter()
+        .enumerate()
+        .map(|(index, shard)| {
+            let mut part = vec![index as u8, options.data_shards, options.parity_shards];
+            part.extend(length.to_be_bytes());
+            part.extend(shard);
+            container::seal_part(&part)
+        })
+        .collect()
+}
+
+/// Number of data shards, number of parity shards, payload length and shard length, which every
+/// part of the same payload agrees on.
+type Layout = (usize, usize, usize, usize);
+
+/// Recovers a payload from the parts made by `encode`, in any order and with any of them
+/// missing or damaged, as long as enough intact parts remain.
+///
+/// Parts are grouped by their layout, since a file can also hold stale parts or parts of another
+/// payload. Groups are tried from the one with the most parts down, and the first that can be
+/// reconstructed wins.
+pub fn decode<'a>(
+    parts: impl IntoIterator<Item = &'a [u8]>,
+    limits: &Limits,
+) -> crate::Result<Vec<u8>> {
+    let mut groups: Vec<(Layout, Vec<Option<Vec<u8>>>)> = Vec::new();
+
+    for part in parts
+        .into_iter()
+        .filter_map(|p| container::open_part(p).ok())
+    {
+        let Some((header, shard)) = part.split_at_checked(SHARD_HEADER_LEN) else {
+            continue;
+        };
+        let index = usize::from(header[0]);
+        let layout = (
+            usize::from(header[1]),
+            usize::from(header[2]),
+            u32::from_be_bytes(header[3..].try_into()?) as usize,
+            shard.len(),
+        );
+
+        let (data_shards, parity_shards, ..) = layout;
+        if index >= data_shards + parity_shards {
+            continue;
+        }
+
+        let position = match groups.iter().position(|(l, _)| *l == layout) {
+            Some(position) => position,
+            None => {
+                groups.push((layout, vec![None; data_shards + parity_shards]));
+                groups.len() - 1
+            }
+        };
+        groups[position].1[index].get_or_insert_with(|| shard.to_vec());
+    }
+
+    // A stable sort keeps the first group found ahead of others of the same size.
+    groups.sort_by_key(|(_, shards)| std::cmp::Reverse(shards.iter().flatten().count()));
+
+    let mut first_error = None;
+    for (layout, shards) in groups {
+        match reconstruct(layout, shards, limits) {
+            Ok(payload) => return Ok(payload),
+            Err(error) => {
+                first_error.get_or_insert(error);
+            }
+        }
+    }
+
+    Err(first_error.unwrap_or_else(|| "could not find any intact payload parts".into()))
+}
+
+/// Rebuilds the payload from the shards of one group, of which some may be missing.
+fn reconstruct(
+    (data_shards, parity_shards, length, shard_len): Layout,
+    mut shards: Vec<Option<Vec<u8>>>,
+    limits: &Limits,
+) -> crate::Result<Vec<u8>> {
+    let found = shards.iter().flatten().count();
+    if found < data_shards {
+        return Err(
+            format!("only {found} of the {data_shards} needed payload parts are intact").into(),
+        );
+    }
+    if length > data_shards * shard_len {
+        return Err("payload parts are inconsistent".into());
+    }
+    limits.check_decompressed_size(length)?;
+
+    ReedSolomon::new(data_shards, parity_shards)?.reconstruct_data(&mut shards)?;
+
+    let mut payload: Vec<u8> = shards
+        .into_iter()
+        .take(data_shards)
+        .flatten()
+        .flatten()
+        .collect();
+    payload.truncate(length);
+    Ok(payload)
+}
+
+/// Finds the data of every chunk of `chunk_type` that holds a payload part, by searching the raw
+/// file bytes for the chunk type followed by the container magic.
+///
+/// This doesn't rely on the file being parseable, so parts are still found when other chunks
+/// are damaged or the file is cut short. A part whose chunk is truncated is skipped.
+pub fn find_parts<'a>(bytes: &'a [u8], chunk_type: &str) -> Vec<&'a [u8]> {
+    let pattern = [chunk_type.as_bytes(), container::MAGIC].concat();
+
+    (4..bytes.len())
+        .filter(|&i| bytes[i..].starts_with(&pattern))
+        .filter_map(|i| {
+            let length = u32::from_be_bytes(bytes[i - 4..i].try_into().ok()?) as usize;
+            let start = i + chunk_type.len();
+            bytes.get(start..start.checked_add(length)?)
+        })
+        .filter(|data| container::open_part(data).is_ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::{tests::PNG_FILE, Chunk, ChunkType, Png};
+    use std::str::FromStr;
+
+    const OPTIONS: FecOptions = FecOptions {
+        data_shards: 4,
+        parity_shards: 2,
+    };
+
+    #[test]
+    fn test_encode_decode() {
+        let payload = b"this message survives losing two of its six parts";
+        let parts = encode(payload, &OPTIONS).unwrap();
+        assert_eq!(parts.len(), 6);
+
+        let all = parts.iter().map(Vec::as_slice);
+        assert_eq!(decode(all, &Limits::default()).unwrap(), payload);
+
+        // Lose one part, corrupt another and shuffle the rest.
+        let mut damaged = parts.clone();
+        damaged.remove(1);
+        damaged[3][container::HEADER_LEN + 9] ^= 0xff;
+        damaged.reverse();
+        let damaged = damaged.iter().map(Vec::as_slice);
+        assert_eq!(decode(damaged, &Limits::default()).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_foreign_part_first() {
+        let payload = b"the real payload comes after a stale part";
+        let stale = encode(b"left over from an earlier run", &OPTIONS).unwrap();
+        let parts = encode(payload, &OPTIONS).unwrap();
+
+        // A stale part first, then the real payload with one part lost.
+        let mixed = [&stale[0]]
+            .into_iter()
+            .chain(&parts[1..])
+            .map(Vec::as_slice);
+        assert_eq!(decode(mixed, &Limits::default()).unwrap(), payload);
+
+        // A part of another payload with a different layout, too few to recover anything from.
+        let other = encode(
+            b"a different message",
+            &FecOptions {
+                data_shards: 2,
+                parity_shards: 1,
+            },
+        )
+        .unwrap();
+        let mixed = other[..1].iter().chain(&parts[..4]).map(Vec::as_slice);
+        assert_eq!(decode(mixed, &Limits::default()).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_too_much_damage() {
+        let parts = encode(b"lost", &OPTIONS).unwrap();
+        let remaining = parts[..3].iter().map(Vec::as_slice);
+
+        assert!(decode(remaining, &Limits::default()).is_err());
+        assert!(decode([], &Limits::default()).is_err());
+    }
+
+    #[test]
+    fn test_find_parts_in_damaged_file() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        for part in encode(b"found in the rubble", &OPTIONS).unwrap() {
+            let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), part);
+            png.insert_chunk_before("IEND", chunk).unwrap();
+        }
+
+        let mut bytes = png.as_bytes();
+        // Break the CRC of a chunk and cut off the last payload part along with IEND.
+        bytes[50] ^= 1;
+        bytes.truncate(bytes.len() - 30);
+
+        let parts = find_parts(&bytes, "ruSt");
+        assert_eq!(parts.len(), 5);
+        assert_eq!(
+            decode(parts, &Limits::default()).unwrap(),
+            b"found in the rubble"
+        );
+    }
+}
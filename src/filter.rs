@@ -0,0 +1,315 @@
+use crate::zlib;
+use std::convert::TryFrom;
+
+/// The five scanline filters defined by PNG filter method 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterType {
+    None = 0,
+    Sub = 1,
+    Up = 2,
+    Average = 3,
+    Paeth = 4,
+}
+
+impl TryFrom<u8> for FilterType {
+    type Error = crate::Error;
+
+    fn try_from(value: u8) -> crate::Result<Self> {
+        match value {
+            0 => Ok(FilterType::None),
+            1 => Ok(FilterType::Sub),
+            2 => Ok(FilterType::Up),
+            3 => Ok(FilterType::Average),
+            4 => Ok(FilterType::Paeth),
+            _ => Err(format!("invalid filter type {value}").into()),
+        }
+    }
+}
+
+fn paeth_predictor(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Reverses `filter` on a single scanline in place.
+///
+/// `prev` is the already unfiltered previous scanline, or all zeroes for the first one, and `bpp`
+/// is the number of bytes per complete pixel, rounded up to one.
+pub fn unfilter_scanline(filter: FilterType, bpp: usize, prev: &[u8], line: &mut [u8]) {
+    match filter {
+        FilterType::None => {}
+        FilterType::Sub => {
+            for i in bpp..line.len() {
+                line[i] = line[i].wrapping_add(line[i - bpp]);
+            }
+        }
+        FilterType::Up => {
+            for (x, b) in line.iter_mut().zip(prev) {
+                *x = x.wrapping_add(*b);
+            }
+        }
+        FilterType::Average => {
+            for i in 0..line.len() {
+                let a = if i >= bpp { line[i - bpp] } else { 0 };
+                let avg = (a as u16 + prev[i] as u16) / 2;
+                line[i] = line[i].wrapping_add(avg as u8);
+            }
+        }
+        FilterType::Paeth => {
+            for i in 0..line.len() {
+                let (a, c) = if i >= bpp {
+                    (line[i - bpp], prev[i - bpp])
+                } else {
+                    (0, 0)
+                };
+                line[i] = line[i].wrapping_add(paeth_predictor(a, prev[i], c));
+            }
+        }
+    }
+}
+
+/// Applies `filter` to a single scanline, appending the filter type byte and filtered bytes to
+/// `out`.
+///
+/// `prev` is the unfiltered previous scanline, or all zeroes for the first one.
+pub fn filter_scanline(
+    filter: FilterType,
+    bpp: usize,
+    prev: &[u8],
+    line: &[u8],
+    out: &mut Vec<u8>,
+) {
+    out.push(filter as u8);
+
+    for i in 0..line.len() {
+        let (a, c) = if i >= bpp {
+            (line[i - bpp], prev[i - bpp])
+        } else {
+            (0, 0)
+        };
+        let b = prev[i];
+
+        let predicted = match filter {
+            FilterType::None => 0,
+            FilterType::Sub => a,
+            FilterType::Up => b,
+            FilterType::Average => ((a as u16 + b as u16) / 2) as u8,
+            FilterType::Paeth => paeth_predictor(a, b, c),
+        };
+
+        out.push(line[i].wrapping_sub(predicted));
+    }
+}
+
+/// How the encoder picks a filter for each scanline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterStrategy {
+    /// Use the same filter for every scanline.
+    Fixed(FilterType),
+    /// Pick the filter whose output has the smallest sum of absolute differences, treating each
+    /// byte as signed. This is the heuristic recommended by the PNG specification.
+    MinSum,
+    /// Compress the scanline with every filter and keep whichever is smallest.
+    BruteForce,
+}
+
+const ALL_FILTERS: [FilterType; 5] = [
+    FilterType::None,
+    FilterType::Sub,
+    FilterType::Up,
+    FilterType::Average,
+    FilterType::Paeth,
+];
+
+fn sum_of_absolute_differences(filtered: &[u8]) -> u64 {
+    filtered[1..]
+        .iter()
+        .map(|&b| (b as i8).unsigned_abs() as u64)
+        .sum()
+}
+
+/// Filters consecutive scanlines of `stride` bytes, prefixing each with its filter type byte.
+pub fn filter(pixels: &[u8], stride: usize, bpp: usize, strategy: FilterStrategy) -> Vec<u8> {
+    if stride == 0 {
+        return Vec::new();
+    }
+
+    let mut out = Vec::with_capacity(pixels.len() + pixels.len() / stride);
+    let mut candidate = Vec::with_capacity(stride + 1);
+    let zeroes = vec![0; stride];
+
+    for (y, line) in pixels.chunks_exact(stride).enumerate() {
+        let prev = if y == 0 {
+            &zeroes[..]
+        } else {
+            &pixels[(y - 1) * stride..y * stride]
+        };
+
+        let filter = match strategy {
+            FilterStrategy::Fixed(filter) => filter,
+            FilterStrategy::MinSum => *ALL_FILTERS
+                .iter()
+                .min_by_key(|&&filter| {
+                    candidate.clear();
+                    filter_scanline(filter, bpp, prev, line, &mut candidate);
+                    sum_of_absolute_differences(&candidate)
+                })
+                .unwrap_or(&FilterType::None),
+            FilterStrategy::BruteForce => *ALL_FILTERS
+                .iter()
+                .min_by_key(|&&filter| {
+                    candidate.clear();
+                    filter_scanline(filter, bpp, prev, line, &mut candidate);
+                    zlib::deflate(&candidate, zlib::DEFAULT_LEVEL).len()
+                })
+                .unwrap_or(&FilterType::None),
+        };
+
+        filter_scanline(filter, bpp, prev, line, &mut out);
+    }
+
+    out
+}
+
+/// Unfilters consecutive scanlines that each start with a filter type byte.
+///
+/// Returns the raw scanlines without their filter type bytes, `stride` bytes per row.
+pub fn unfilter(filtered: &[u8], stride: usize, bpp: usize) -> crate::Result<Vec<u8>> {
+    if stride == 0 {
+        return Ok(Vec::new());
+    }
+
+    if !filtered.len().is_multiple_of(stride + 1) {
+        return Err("filtered data is not a whole number of scanlines".into());
+    }
+
+    let rows = filtered.len() / (stride + 1);
+    let mut pixels = vec![0; rows * stride];
+    let zeroes = vec![0; stride];
+
+    for (y, row) in filtered.chunks_exact(stride + 1).enumerate() {
+        let filter = FilterType::try_from(row[0])?;
+        let (done, rest) = pixels.split_at_mut(y * stride);
+        let prev = if y == 0 {
+            &zeroes
+        } else {
+            &done[done.len() - stride..]
+        };
+        let line = &mut rest[..stride];
+
+        line.copy_from_slice(&row[1..]);
+        unfilter_scanline(filter, bpp, prev, line);
+    }
+
+    Ok(pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unfilter_none() {
+        let mut line = [1, 2, 3, 4];
+        unfilter_scanline(FilterType::None, 1, &[9, 9, 9, 9], &mut line);
+        assert_eq!(line, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_unfilter_sub() {
+        let mut line = [10, 20, 1, 2, 255, 255];
+        unfilter_scanline(FilterType::Sub, 2, &[0; 6], &mut line);
+        assert_eq!(line, [10, 20, 11, 22, 10, 21]);
+    }
+
+    #[test]
+    fn test_unfilter_up() {
+        let mut line = [1, 2, 3];
+        unfilter_scanline(FilterType::Up, 1, &[10, 20, 255], &mut line);
+        assert_eq!(line, [11, 22, 2]);
+    }
+
+    #[test]
+    fn test_unfilter_average() {
+        let mut line = [1, 2, 3];
+        unfilter_scanline(FilterType::Average, 1, &[10, 20, 30], &mut line);
+        // 1 + 10/2, 2 + (6 + 20)/2, 3 + (15 + 30)/2
+        assert_eq!(line, [6, 15, 25]);
+    }
+
+    #[test]
+    fn test_unfilter_paeth() {
+        let mut line = [1, 2, 3];
+        unfilter_scanline(FilterType::Paeth, 1, &[10, 20, 30], &mut line);
+        // The first byte predicts from b, the rest from whichever of a, b, c is closest to a + b - c.
+        assert_eq!(line, [11, 22, 33]);
+    }
+
+    #[test]
+    fn test_paeth_predictor() {
+        assert_eq!(paeth_predictor(10, 20, 10), 20);
+        assert_eq!(paeth_predictor(20, 10, 10), 20);
+        assert_eq!(paeth_predictor(10, 20, 30), 10);
+        assert_eq!(paeth_predictor(30, 20, 10), 30);
+    }
+
+    #[test]
+    fn test_unfilter_rows() {
+        #[rustfmt::skip]
+        let filtered = [
+            0, 1, 2, 3,
+            2, 1, 1, 1,
+            1, 5, 1, 1,
+        ];
+
+        let pixels = unfilter(&filtered, 3, 1).unwrap();
+        assert_eq!(pixels, [1, 2, 3, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn test_unfilter_invalid() {
+        assert!(unfilter(&[5, 1, 2, 3], 3, 1).is_err());
+        assert!(unfilter(&[0, 1, 2], 3, 1).is_err());
+    }
+
+    #[test]
+    fn test_filter_round_trip() {
+        let pixels: Vec<u8> = (0..120u32).map(|i| (i * i % 251) as u8).collect();
+        let strategies = [
+            FilterStrategy::Fixed(FilterType::None),
+            FilterStrategy::Fixed(FilterType::Sub),
+            FilterStrategy::Fixed(FilterType::Up),
+            FilterStrategy::Fixed(FilterType::Average),
+            FilterStrategy::Fixed(FilterType::Paeth),
+            FilterStrategy::MinSum,
+            FilterStrategy::BruteForce,
+        ];
+
+        for strategy in strategies {
+            let filtered = filter(&pixels, 12, 3, strategy);
+            assert_eq!(filtered.len(), 130);
+            assert_eq!(unfilter(&filtered, 12, 3).unwrap(), pixels);
+        }
+    }
+
+    #[test]
+    fn test_min_sum_prefers_smooth_filter() {
+        // Every row is the same, so Up turns everything after the first row into zeroes.
+        let pixels: Vec<u8> = [10, 200, 30, 140].repeat(4);
+        let filtered = filter(&pixels, 4, 1, FilterStrategy::MinSum);
+
+        for row in filtered.chunks(5).skip(1) {
+            assert_eq!(row, [FilterType::Up as u8, 0, 0, 0, 0]);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use crate::chunk_type::ChunkType;
+use std::fmt::{self, Display, Formatter};
+
+/// The file formats that share the PNG chunk layout and differ in their signature.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Png,
+    /// Multiple-image Network Graphics, which holds whole PNG and JNG datastreams.
+    Mng,
+    /// JPEG Network Graphics, a JPEG image with an optional PNG-style alpha channel.
+    Jng,
+}
+
+impl Format {
+    const ALL: [Format; 3] = [Format::Png, Format::Mng, Format::Jng];
+
+    /// The eight bytes every file of this format starts with.
+    pub fn signature(&self) -> &'static [u8; 8] {
+        match self {
+            Format::Png => &[137, 80, 78, 71, 13, 10, 26, 10],
+            Format::Mng => &[138, 77, 78, 71, 13, 10, 26, 10],
+            Format::Jng => &[139, 74, 78, 71, 13, 10, 26, 10],
+        }
+    }
+
+    /// Finds the format whose signature `bytes` start with.
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|format| bytes.starts_with(format.signature()))
+    }
+
+    /// The chunk that has to come first.
+    pub fn header_chunk(&self) -> &'static str {
+        match self {
+            Format::Png => "IHDR",
+            Format::Mng => "MHDR",
+            Format::Jng => "JHDR",
+        }
+    }
+
+    /// The chunk that ends the datastream. An MNG can hold PNG datastreams that end in IEND, so
+    /// only MEND ends the MNG itself.
+    pub fn end_chunk(&self) -> &'static str {
+        match self {
+            Format::Png | Format::Jng => "IEND",
+            Format::Mng => "MEND",
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Format::Png => "PNG",
+            Format::Mng => "MNG",
+            Format::Jng => "JNG",
+        })
+    }
+}
+
+/// Checks chunk order while a datastream is parsed, for the formats that require it.
+///
+/// PNG files are only checked when their image is decoded, as they always have been, while MNG
+/// and JNG files must start with their header chunk and end with their end chunk. A JNG also
+/// needs image data before its IEND.
+#[derive(Debug)]
+pub struct OrderCheck {
+    format: Format,
+    count: usize,
+    seen_jdat: bool,
+    ended: bool,
+}
+
+impl OrderCheck {
+    pub fn new(format: Format) -> Self {
+        Self {
+            format,
+            count: 0,
+            seen_jdat: false,
+            ended: false,
+        }
+    }
+
+    /// Checks the next chunk, returning whether it ends the datastream.
+    pub fn next(&mut self, chunk_type: &ChunkType) -> crate::Result<bool> {
+        let bytes = chunk_type.bytes();
+        self.count += 1;
+        self.ended = bytes == self.format.end_chunk().as_bytes();
+
+        if self.format == Format::Png {
+            return Ok(self.ended);
+        }
+
+        let header = self.format.header_chunk();
+        if (self.count == 1) != (bytes == header.as_bytes()) {
+            return Err(format!(
+                "{} datastream must start with one {header} chunk",
+                self.format
+            )
+            .into());
+        }
+
+        match (self.format, &bytes) {
+            (Format::Jng, b"JDAT") => self.seen_jdat = true,
+            (Format::Jng, b"IEND") if !self.seen_jdat => {
+                return Err("JNG datastream has no JDAT chunk".into());
+            }
+            _ => {}
+        }
+
+        Ok(self.ended)
+    }
+
+    /// Checks that the datastream was not cut short, once every chunk has been read.
+    pub fn finish(&self) -> crate::Result<()> {
+        if self.format != Format::Png && !self.ended {
+            return Err(format!(
+                "{} datastream is missing its {} chunk",
+                self.format,
+                self.format.end_chunk()
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+use crate::{
+    apng::Animation,
+    image::{EncodeOptions, Image},
+    lsb::{self, EmbedReport, LsbOptions},
+    png::{Limits, Png},
+};
+
+/// Indices of the frames that carry the payload, in sequence number order.
+///
+/// The default image is left alone when it is part of the animation, so viewers without APNG
+/// support show an untouched picture.
+fn carriers(animation: &Animation) -> crate::Result<Vec<usize>> {
+    let skip = usize::from(animation.default_is_frame);
+    let mut frames: Vec<usize> = (skip..animation.frames.len()).collect();
+    frames.sort_by_key(|&i| animation.frames[i].control.sequence_number);
+
+    if frames.is_empty() {
+        return Err("the animation has no frames besides the default image".into());
+    }
+
+    Ok(frames)
+}
+
+/// Decodes every carrier frame along with its LSB capacity.
+fn decode_carriers(
+    png: &Png,
+    animation: &Animation,
+    options: &LsbOptions,
+    limits: &Limits,
+) -> crate::Result<Vec<(usize, Image, usize)>> {
+    carriers(animation)?
+        .into_iter()
+        .map(|i| {
+            let image = Image::decode(&animation.extract(png, i)?, limits)?;
+            let capacity = lsb::capacity(&image, options)?;
+            Ok((i, image, capacity))
+        })
+        .collect()
+}
+
+/// Number of payload bytes that fit in the frames of an animated PNG.
+pub fn capacity(png: &Png, options: &LsbOptions, limits: &Limits) -> crate::Result<usize> {
+    let animation = Animation::from_png(png)?;
+    let carriers = decode_carriers(png, &animation, options, limits)?;
+
+    Ok(carriers.iter().map(|(.., capacity)| capacity).sum())
+}
+
+/// Splits `payload` over the low bits of the animation frames after the default image.
+///
+/// Each frame holds the next part of the payload with its own length prefix, and frames that
+/// aren't needed get an empty part, so `extract` can join the parts back together in sequence
+/// number order.
+pub fn embed(
+    png: &mut Png,
+    payload: &[u8],
+    options: &LsbOptions,
+    limits: &Limits,
+) -> crate::Result<EmbedReport> {
+    let mut animation = Animation::from_png(png)?;
+    let carriers = decode_carriers(png, &animation, options, limits)?;
+
+    let capacity: usize = carriers.iter().map(|(.., capacity)| capacity).sum();
+    if payload.len() > capacity {
+        return Err(format!(
+            "message is {} bytes, but the frames only have room for {capacity}",
+            payload.len()
+        )
+        .into());
+    }
+
+    let mut report = EmbedReport {
+        bits: 0,
+        samples: 0,
+        modified: 0,
+    };
+    let mut remaining = payload;
+    for (i, mut image, capacity) in carriers {
+        let (part, rest) = remaining.split_at(capacity.min(remaining.len()));
+        remaining = rest;
+
+        let frame_report = lsb::embed(&mut image, part, options)?;
+        report.bits += frame_report.bits;
+        report.samples += frame_report.samples;
+        report.modified += frame_report.modified;
+
+        animation.frames[i].data = image
+            .encode(&EncodeOptions::default())?
+            .iter()
+            .flat_map(|chunk| chunk.data().to_vec())
+            .collect();
+    }
+
+    animation.write_into(png)?;
+
+    Ok(report)
+}
+
+/// Reads back a payload hidden by `embed`.
+pub fn extract(png: &Png, options: &LsbOptions, limits: &Limits) -> crate::Result<Vec<u8>> {
+    let animation = Animation::from_png(png)?;
+
+    let mut payload = Vec::new();
+    for (_, image, _) in decode_carriers(png, &animation, options, limits)? {
+        payload.extend(lsb::extract(&image, options)?);
+    }
+
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{apng, png::tests::PNG_FILE};
+
+    fn testing_apng() -> Png {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let frames: Vec<Png> = (0..3)
+            .map(|_| Png::from_chunks(png.chunks().to_vec()))
+            .collect();
+
+        apng::assemble(&frames, 100, 0).unwrap()
+    }
+
+    fn options() -> LsbOptions {
+        LsbOptions {
+            bits: 1,
+            channels: "rgb".parse().unwrap(),
+            passphrase: None,
+            matrix: None,
+        }
+    }
+
+    #[test]
+    fn test_embed_extract() {
+        let mut apng = testing_apng();
+        let capacity = capacity(&apng, &options(), &Limits::default()).unwrap();
+        let default_image = Image::decode(&apng, &Limits::default()).unwrap();
+
+        // Longer than one frame can hold, so it has to be split over both later frames.
+        let payload: Vec<u8> = (0..capacity).map(|i| i as u8).collect();
+        let report = embed(&mut apng, &payload, &options(), &Limits::default()).unwrap();
+        assert!(report.modified > 0);
+
+        let apng = Png::try_from(&apng.as_bytes()[..]).unwrap();
+        assert_eq!(
+            extract(&apng, &options(), &Limits::default()).unwrap(),
+            payload
+        );
+        assert_eq!(
+            Image::decode(&apng, &Limits::default()).unwrap(),
+            default_image
+        );
+    }
+
+    #[test]
+    fn test_too_long() {
+        let mut apng = testing_apng();
+        let capacity = capacity(&apng, &options(), &Limits::default()).unwrap();
+
+        let payload = vec![0; capacity + 1];
+        assert!(embed(&mut apng, &payload, &options(), &Limits::default()).is_err());
+    }
+}
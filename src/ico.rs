@@ -0,0 +1,272 @@
+use crate::png::{Format, Limits};
+use std::{
+    fmt::{self, Display, Formatter},
+    ops::Range,
+};
+
+/// Length of the ICONDIR header.
+const HEADER_LEN: usize = 6;
+/// Length of each ICONDIRENTRY.
+const ENTRY_LEN: usize = 16;
+
+/// Whether a file holds icons or cursors, which only changes the meaning of two entry fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IconKind {
+    Icon = 1,
+    Cursor = 2,
+}
+
+impl Display for IconKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            IconKind::Icon => "ICO",
+            IconKind::Cursor => "CUR",
+        })
+    }
+}
+
+/// One image in the directory of an ICO or CUR file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IconEntry {
+    /// Width, height, color count and reserved bytes, as stored.
+    size: [u8; 4],
+    /// Color planes for icons, or the horizontal hotspot for cursors.
+    pub planes: u16,
+    /// Bits per pixel for icons, or the vertical hotspot for cursors.
+    pub bit_count: u16,
+    /// Where the image is in the file.
+    range: Range<usize>,
+}
+
+impl IconEntry {
+    /// Width in pixels, where a stored 0 means 256.
+    pub fn width(&self) -> u32 {
+        match self.size[0] {
+            0 => 256,
+            width => u32::from(width),
+        }
+    }
+
+    /// Height in pixels, where a stored 0 means 256.
+    pub fn height(&self) -> u32 {
+        match self.size[1] {
+            0 => 256,
+            height => u32::from(height),
+        }
+    }
+
+    /// The image, which is either a PNG or a headerless BMP.
+    pub fn data<'a>(&self, file: &'a [u8]) -> &'a [u8] {
+        &file[self.range.clone()]
+    }
+
+    pub fn is_png(&self, file: &[u8]) -> bool {
+        self.data(file).starts_with(Format::Png.signature())
+    }
+}
+
+/// The directory of an ICO or CUR file.
+///
+/// Entries refer to the file they were parsed from rather than copying it, so the same bytes
+/// have to be passed back in to read or replace an image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Icon {
+    kind: IconKind,
+    entries: Vec<IconEntry>,
+}
+
+/// Whether `bytes` look like an ICO or CUR file, judging by the header.
+pub fn is_icon(bytes: &[u8]) -> bool {
+    matches!(bytes, [0, 0, 1 | 2, 0, count_lo, count_hi, ..] if u16::from_le_bytes([*count_lo, *count_hi]) > 0)
+}
+
+impl Icon {
+    /// Reads the directory of an ICO or CUR file and checks that every image lies within it.
+    pub fn parse(bytes: &[u8], limits: &Limits) -> crate::Result<Self> {
+        limits.check_file_size(bytes.len() as u64)?;
+        if !is_icon(bytes) {
+            return Err("not an ICO or CUR file".into());
+        }
+
+        let kind = match bytes[2] {
+            1 => IconKind::Icon,
+            _ => IconKind::Cursor,
+        };
+        let count = usize::from(u16::from_le_bytes([bytes[4], bytes[5]]));
+        let directory = bytes
+            .get(HEADER_LEN..HEADER_LEN + count * ENTRY_LEN)
+            .ok_or("icon directory is truncated")?;
+
+        let entries = directory
+            .chunks_exact(ENTRY_LEN)
+            .enumerate()
+            .map(|(i, entry)| {
+                let u16_at = |at: usize| u16::from_le_bytes([entry[at], entry[at + 1]]);
+                let u32_at = |at: usize| {
+                    u32::from_le_bytes([entry[at], entry[at + 1], entry[at + 2], entry[at + 3]])
+                        as usize
+                };
+
+                let (size, offset) = (u32_at(8), u32_at(12));
+                let range = offset..offset.saturating_add(size);
+                if range.end > bytes.len() || offset < directory.len() + HEADER_LEN {
+                    return Err(format!("icon entry {i} lies outside of the file").into());
+                }
+
+                Ok(IconEntry {
+                    size: entry[0..4].try_into()?,
+                    planes: u16_at(4),
+                    bit_count: u16_at(6),
+                    range,
+                })
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+
+        Ok(Self { kind, entries })
+    }
+
+    pub fn kind(&self) -> IconKind {
+        self.kind
+    }
+
+    pub fn entries(&self) -> &[IconEntry] {
+        &self.entries
+    }
+
+    /// Picks the entry to work on, which must hold a PNG: `index` if given, or else the first
+    /// PNG entry.
+    pub fn png_entry(&self, file: &[u8], index: Option<usize>) -> crate::Result<usize> {
+        match index {
+            Some(i) => match self.entries.get(i) {
+                Some(entry) if entry.is_png(file) => Ok(i),
+                Some(_) => Err(format!("icon entry {i} is a BMP image, not a PNG").into()),
+                None => Err(format!(
+                    "there is no icon entry {i}, the file has {} entries",
+                    self.entries.len()
+                )
+                .into()),
+            },
+            None => self
+                .entries
+                .iter()
+                .position(|entry| entry.is_png(file))
+                .ok_or_else(|| "the icon file has no PNG entries".into()),
+        }
+    }
+
+    /// Rebuilds `file` with the image of entry `index` replaced by `data`.
+    ///
+    /// Images are laid out one after another in directory order, with the offset and size of
+    /// every entry updated to match.
+    pub fn replace_entry(&self, file: &[u8], index: usize, data: &[u8]) -> crate::Result<Vec<u8>> {
+        let images: Vec<&[u8]> = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| if i == index { data } else { entry.data(file) })
+            .collect();
+
+        let mut header = vec![0, 0];
+        header.extend((self.kind as u16).to_le_bytes());
+        header.extend(u16::try_from(self.entries.len())?.to_le_bytes());
+
+        let mut directory = Vec::with_capacity(self.entries.len() * ENTRY_LEN);
+        let mut offset = HEADER_LEN + self.entries.len() * ENTRY_LEN;
+        for (entry, image) in self.entries.iter().zip(&images) {
+            let too_large = |_| "icon file is too large";
+            directory.extend(entry.size);
+            directory.extend(entry.planes.to_le_bytes());
+            directory.extend(entry.bit_count.to_le_bytes());
+            directory.extend(u32::try_from(image.len()).map_err(too_large)?.to_le_bytes());
+            directory.extend(u32::try_from(offset).map_err(too_large)?.to_le_bytes());
+            offset += image.len();
+        }
+
+        Ok([header, directory, images.concat()].concat())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::PNG_FILE;
+
+    /// An icon with a BMP entry followed by a PNG entry, whose images are stored in reverse
+    /// order to make sure offsets are followed rather than assumed.
+    fn testing_icon(kind: u8) -> Vec<u8> {
+        let bmp = [40u8; 48];
+        let directory_end = HEADER_LEN + 2 * ENTRY_LEN;
+        let png_offset = directory_end;
+        let bmp_offset = png_offset + PNG_FILE.len();
+
+        let entry = |size: u8, bit_count: u16, len: usize, offset: usize| {
+            [
+                &[size, size, 0, 0][..],
+                &1u16.to_le_bytes(),
+                &bit_count.to_le_bytes(),
+                &(len as u32).to_le_bytes(),
+                &(offset as u32).to_le_bytes(),
+            ]
+            .concat()
+        };
+
+        [
+            &[0, 0, kind, 0, 2, 0][..],
+            &entry(16, 32, bmp.len(), bmp_offset),
+            &entry(0, 32, PNG_FILE.len(), png_offset),
+            &PNG_FILE,
+            &bmp,
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn test_parse() {
+        let bytes = testing_icon(1);
+        let icon = Icon::parse(&bytes, &Limits::default()).unwrap();
+
+        assert_eq!(icon.kind(), IconKind::Icon);
+        assert_eq!(icon.entries().len(), 2);
+        assert_eq!(icon.entries()[0].width(), 16);
+        assert_eq!(icon.entries()[1].height(), 256);
+        assert!(!icon.entries()[0].is_png(&bytes));
+        assert_eq!(icon.entries()[1].data(&bytes), PNG_FILE);
+    }
+
+    #[test]
+    fn test_png_entry() {
+        let bytes = testing_icon(2);
+        let icon = Icon::parse(&bytes, &Limits::default()).unwrap();
+
+        assert_eq!(icon.kind(), IconKind::Cursor);
+        assert_eq!(icon.png_entry(&bytes, None).unwrap(), 1);
+        assert_eq!(icon.png_entry(&bytes, Some(1)).unwrap(), 1);
+        assert!(icon.png_entry(&bytes, Some(0)).is_err());
+        assert!(icon.png_entry(&bytes, Some(2)).is_err());
+    }
+
+    #[test]
+    fn test_replace_entry() {
+        let bytes = testing_icon(1);
+        let icon = Icon::parse(&bytes, &Limits::default()).unwrap();
+
+        let longer = [&PNG_FILE[..], b"and some more"].concat();
+        let rebuilt = icon.replace_entry(&bytes, 1, &longer).unwrap();
+        let reparsed = Icon::parse(&rebuilt, &Limits::default()).unwrap();
+
+        assert_eq!(reparsed.entries()[0].data(&rebuilt), [40; 48]);
+        assert_eq!(reparsed.entries()[1].data(&rebuilt), longer);
+        assert_eq!(reparsed.entries()[1].width(), 256);
+        assert_eq!(rebuilt.len(), bytes.len() + 13);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(!is_icon(&PNG_FILE));
+        assert!(!is_icon(&[0, 0, 1, 0, 0, 0]));
+
+        let mut bytes = testing_icon(1);
+        bytes.truncate(bytes.len() - 1);
+        assert!(Icon::parse(&bytes, &Limits::default()).is_err());
+    }
+}
@@ -0,0 +1,584 @@
+use crate::{
+    filter::{self, FilterStrategy},
+    interlace,
+    png::{Chunk, ChunkType, Format, Limits, Png},
+    zlib,
+};
+use std::convert::TryFrom;
+
+/// The color types allowed in an IHDR chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorType {
+    Grayscale = 0,
+    Rgb = 2,
+    Indexed = 3,
+    GrayscaleAlpha = 4,
+    Rgba = 6,
+}
+
+impl ColorType {
+    /// Number of samples that make up one pixel.
+    pub fn channels(&self) -> usize {
+        match self {
+            ColorType::Grayscale | ColorType::Indexed => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+
+    fn allowed_bit_depths(&self) -> &'static [u8] {
+        match self {
+            ColorType::Grayscale => &[1, 2, 4, 8, 16],
+            ColorType::Indexed => &[1, 2, 4, 8],
+            ColorType::Rgb | ColorType::GrayscaleAlpha | ColorType::Rgba => &[8, 16],
+        }
+    }
+}
+
+impl TryFrom<u8> for ColorType {
+    type Error = crate::Error;
+
+    fn try_from(value: u8) -> crate::Result<Self> {
+        match value {
+            0 => Ok(ColorType::Grayscale),
+            2 => Ok(ColorType::Rgb),
+            3 => Ok(ColorType::Indexed),
+            4 => Ok(ColorType::GrayscaleAlpha),
+            6 => Ok(ColorType::Rgba),
+            _ => Err(format!("invalid color type {value}").into()),
+        }
+    }
+}
+
+/// The contents of an IHDR chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ihdr {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+    pub interlace_method: u8,
+}
+
+impl Ihdr {
+    /// Largest width or height allowed by the PNG specification.
+    const MAX_DIMENSION: u32 = i32::MAX as u32;
+
+    pub fn bits_per_pixel(&self) -> usize {
+        self.color_type.channels() * self.bit_depth as usize
+    }
+
+    /// Bytes per complete pixel rounded up to one, which is the distance filters look back.
+    pub fn filter_bpp(&self) -> usize {
+        self.bits_per_pixel().div_ceil(8)
+    }
+
+    /// Number of bytes in an unfiltered scanline `width` pixels wide.
+    pub fn stride_for(&self, width: u32) -> crate::Result<usize> {
+        (width as usize)
+            .checked_mul(self.bits_per_pixel())
+            .map(|bits| bits.div_ceil(8))
+            .ok_or_else(|| "image is too wide".into())
+    }
+
+    /// Number of bytes in a full-width unfiltered scanline.
+    pub fn stride(&self) -> crate::Result<usize> {
+        self.stride_for(self.width)
+    }
+
+    pub fn is_interlaced(&self) -> bool {
+        self.interlace_method == 1
+    }
+
+    /// Width and height of each reduced image stored in the IDAT stream, which is the whole image
+    /// unless it is Adam7 interlaced.
+    pub fn passes(&self) -> Vec<(u32, u32)> {
+        if self.is_interlaced() {
+            interlace::pass_dimensions(self.width, self.height).to_vec()
+        } else {
+            vec![(self.width, self.height)]
+        }
+    }
+
+    /// Number of bytes a pass takes up once filtered, including the filter type bytes.
+    ///
+    /// Empty passes have no scanlines, so they don't take up any bytes.
+    pub fn filtered_pass_len(&self, width: u32, height: u32) -> crate::Result<usize> {
+        if width == 0 || height == 0 {
+            return Ok(0);
+        }
+
+        (self.stride_for(width)? + 1)
+            .checked_mul(height as usize)
+            .ok_or_else(|| "image is too large".into())
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        self.width
+            .to_be_bytes()
+            .iter()
+            .chain(self.height.to_be_bytes().iter())
+            .chain(&[
+                self.bit_depth,
+                self.color_type as u8,
+                0,
+                0,
+                self.interlace_method,
+            ])
+            .copied()
+            .collect()
+    }
+
+    pub fn as_chunk(&self) -> Chunk {
+        Chunk::new(ChunkType::from_bytes(*b"IHDR"), self.as_bytes())
+    }
+}
+
+impl TryFrom<&[u8]> for Ihdr {
+    type Error = crate::Error;
+
+    fn try_from(value: &[u8]) -> crate::Result<Self> {
+        let value: &[u8; 13] = value
+            .try_into()
+            .map_err(|_| "IHDR chunk must be 13 bytes long")?;
+
+        let width = u32::from_be_bytes(value[0..4].try_into()?);
+        let height = u32::from_be_bytes(value[4..8].try_into()?);
+        let bit_depth = value[8];
+        let color_type = ColorType::try_from(value[9])?;
+
+        if width == 0 || height == 0 || width > Self::MAX_DIMENSION || height > Self::MAX_DIMENSION
+        {
+            return Err(format!("invalid image dimensions {width}x{height}").into());
+        }
+
+        if !color_type.allowed_bit_depths().contains(&bit_depth) {
+            return Err(
+                format!("bit depth {bit_depth} is not allowed for {color_type:?} images").into(),
+            );
+        }
+
+        if value[10] != 0 {
+            return Err("unknown compression method".into());
+        }
+
+        if value[11] != 0 {
+            return Err("unknown filter method".into());
+        }
+
+        if value[12] > 1 {
+            return Err("unknown interlace method".into());
+        }
+
+        Ok(Self {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            interlace_method: value[12],
+        })
+    }
+}
+
+/// Settings used when compressing pixels back into IDAT chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodeOptions {
+    pub filter: FilterStrategy,
+    /// Compression level from 0 (stored) to 10 (smallest).
+    pub compression_level: u8,
+    /// Largest amount of compressed data to put in a single IDAT chunk.
+    pub idat_size: usize,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        Self {
+            filter: FilterStrategy::MinSum,
+            compression_level: zlib::DEFAULT_LEVEL,
+            idat_size: 8192,
+        }
+    }
+}
+
+/// The decoded pixels of a PNG.
+///
+/// Pixels are kept exactly as PNG lays them out once filtering is undone: packed at the image's
+/// bit depth, samples in big-endian order, with every row starting on a byte boundary and taking
+/// up `stride` bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    ihdr: Ihdr,
+    stride: usize,
+    pixels: Vec<u8>,
+}
+
+impl Image {
+    /// Creates an image from unfiltered pixels laid out as described by `ihdr`.
+    pub fn new(ihdr: Ihdr, pixels: Vec<u8>) -> crate::Result<Self> {
+        let stride = ihdr.stride()?;
+        if Some(pixels.len()) != stride.checked_mul(ihdr.height as usize) {
+            return Err("pixel buffer does not match the image dimensions".into());
+        }
+
+        Ok(Self {
+            ihdr,
+            stride,
+            pixels,
+        })
+    }
+
+    /// Joins every IDAT chunk of `png`, inflates the result and reverses the scanline filters.
+    ///
+    /// Adam7 interlaced images are de-interlaced, so the result is always at full resolution.
+    pub fn decode(png: &Png, limits: &Limits) -> crate::Result<Self> {
+        if png.format() != Format::Png {
+            return Err(format!("{} files don't hold a single PNG image", png.format()).into());
+        }
+
+        let ihdr = png.chunk_by_type("IHDR").ok_or("missing IHDR chunk")?;
+        let ihdr = Ihdr::try_from(ihdr.data())?;
+        let stride = ihdr.stride()?;
+
+        let pass_lens = ihdr
+            .passes()
+            .into_iter()
+            .map(|(width, height)| ihdr.filtered_pass_len(width, height))
+            .collect::<crate::Result<Vec<_>>>()?;
+        let filtered_len = pass_lens
+            .iter()
+            .try_fold(0usize, |total, len| total.checked_add(*len))
+            .ok_or("image is too large")?;
+        limits.check_decompressed_size(filtered_len)?;
+
+        let idat = png.idat_data();
+
+        if idat.is_empty() {
+            return Err("missing IDAT chunk".into());
+        }
+
+        let filtered = zlib::inflate(&idat, limits)?;
+        let filtered = filtered
+            .get(..filtered_len)
+            .ok_or("image data is truncated")?;
+
+        let mut passes = Vec::with_capacity(pass_lens.len());
+        let mut offset = 0;
+        for ((width, _), len) in ihdr.passes().into_iter().zip(pass_lens) {
+            let pass = &filtered[offset..offset + len];
+            passes.push(filter::unfilter(
+                pass,
+                ihdr.stride_for(width)?,
+                ihdr.filter_bpp(),
+            )?);
+            offset += len;
+        }
+
+        let pixels = if ihdr.is_interlaced() {
+            interlace::deinterlace(&ihdr, &passes)?
+        } else {
+            passes.swap_remove(0)
+        };
+
+        Ok(Self {
+            ihdr,
+            stride,
+            pixels,
+        })
+    }
+
+    pub fn ihdr(&self) -> &Ihdr {
+        &self.ihdr
+    }
+
+    pub fn width(&self) -> u32 {
+        self.ihdr.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.ihdr.height
+    }
+
+    /// Number of bytes in each row of `pixels`.
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [u8] {
+        &mut self.pixels
+    }
+
+    pub fn row(&self, y: u32) -> &[u8] {
+        let start = y as usize * self.stride;
+        &self.pixels[start..start + self.stride]
+    }
+
+    /// Filters and compresses the pixels into IDAT chunks of at most `options.idat_size` bytes.
+    ///
+    /// Interlaced images are split back into their Adam7 passes first, so the result keeps the
+    /// interlace method of the IHDR.
+    pub fn encode(&self, options: &EncodeOptions) -> crate::Result<Vec<Chunk>> {
+        if options.idat_size == 0 {
+            return Err("IDAT size must be greater than zero".into());
+        }
+
+        let passes = if self.ihdr.is_interlaced() {
+            interlace::interlace(&self.ihdr, &self.pixels)?
+        } else {
+            vec![self.pixels.clone()]
+        };
+
+        let mut filtered = Vec::new();
+        for (pass, (width, _)) in passes.iter().zip(self.ihdr.passes()) {
+            let stride = self.ihdr.stride_for(width)?;
+            filtered.extend(filter::filter(
+                pass,
+                stride,
+                self.ihdr.filter_bpp(),
+                options.filter,
+            ));
+        }
+
+        let compressed = zlib::deflate(&filtered, options.compression_level);
+
+        Ok(compressed
+            .chunks(options.idat_size)
+            .map(|data| Chunk::new(ChunkType::from_bytes(*b"IDAT"), data.to_vec()))
+            .collect())
+    }
+
+    /// Replaces the IHDR and IDAT chunks of `png` with this image, keeping every other chunk in
+    /// place.
+    pub fn encode_into(&self, png: &mut Png, options: &EncodeOptions) -> crate::Result<()> {
+        png.replace_chunks("IHDR", vec![self.ihdr.as_chunk()])?;
+        png.replace_chunks("IDAT", self.encode(options)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::FilterType;
+    use std::str::FromStr;
+
+    fn ihdr_bytes(width: u32, height: u32, bit_depth: u8, color_type: u8) -> Vec<u8> {
+        width
+            .to_be_bytes()
+            .iter()
+            .chain(height.to_be_bytes().iter())
+            .chain([bit_depth, color_type, 0, 0, 0].iter())
+            .copied()
+            .collect()
+    }
+
+    fn testing_png(ihdr: Vec<u8>, filtered: &[u8], idat_count: usize) -> Png {
+        let compressed = zlib::deflate(filtered, zlib::DEFAULT_LEVEL);
+        let mut chunks = vec![Chunk::new(ChunkType::from_str("IHDR").unwrap(), ihdr)];
+
+        for part in compressed.chunks(compressed.len().div_ceil(idat_count)) {
+            chunks.push(Chunk::new(
+                ChunkType::from_str("IDAT").unwrap(),
+                part.to_vec(),
+            ));
+        }
+
+        chunks.push(Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]));
+        Png::from_chunks(chunks)
+    }
+
+    #[test]
+    fn test_ihdr_from_bytes() {
+        let ihdr = Ihdr::try_from(ihdr_bytes(50, 40, 8, 6).as_slice()).unwrap();
+
+        assert_eq!(ihdr.width, 50);
+        assert_eq!(ihdr.height, 40);
+        assert_eq!(ihdr.color_type, ColorType::Rgba);
+        assert_eq!(ihdr.bits_per_pixel(), 32);
+        assert_eq!(ihdr.stride().unwrap(), 200);
+    }
+
+    #[test]
+    fn test_ihdr_invalid() {
+        assert!(Ihdr::try_from(ihdr_bytes(0, 1, 8, 6).as_slice()).is_err());
+        assert!(Ihdr::try_from(ihdr_bytes(1, 1, 4, 2).as_slice()).is_err());
+        assert!(Ihdr::try_from(ihdr_bytes(1, 1, 16, 3).as_slice()).is_err());
+        assert!(Ihdr::try_from(ihdr_bytes(1, 1, 8, 5).as_slice()).is_err());
+        assert!(Ihdr::try_from(&ihdr_bytes(1, 1, 8, 6)[..12]).is_err());
+    }
+
+    #[test]
+    fn test_sub_byte_stride() {
+        let ihdr = Ihdr::try_from(ihdr_bytes(10, 1, 2, 0).as_slice()).unwrap();
+
+        assert_eq!(ihdr.stride().unwrap(), 3);
+        assert_eq!(ihdr.filter_bpp(), 1);
+    }
+
+    #[test]
+    fn test_decode_image_file() {
+        let png = Png::try_from(crate::png::tests::PNG_FILE.as_slice()).unwrap();
+        let image = Image::decode(&png, &Limits::default()).unwrap();
+
+        assert_eq!(image.stride(), 200);
+        assert_eq!(image.pixels().len(), 200 * 50);
+        // Checked against an independent decoder.
+        assert_eq!(&image.row(0)[..4], &[0, 0, 0, 0]);
+        assert_eq!(&image.row(25)[100..104], &[240, 240, 240, 255]);
+        assert_eq!(
+            image.pixels().iter().map(|&b| b as u64).sum::<u64>(),
+            680403
+        );
+    }
+
+    #[test]
+    fn test_decode_split_idat() {
+        #[rustfmt::skip]
+        let filtered = [
+            0, 0b1011_0001, 0b0100_0000,
+            2, 0b0000_0001, 0b1000_0000,
+        ];
+
+        let png = testing_png(ihdr_bytes(5, 2, 2, 0), &filtered, 3);
+        let image = Image::decode(&png, &Limits::default()).unwrap();
+
+        assert_eq!(image.stride(), 2);
+        assert_eq!(
+            image.pixels(),
+            [0b1011_0001, 0b0100_0000, 0b1011_0010, 0b1100_0000]
+        );
+    }
+
+    #[test]
+    fn test_decode_sixteen_bit() {
+        #[rustfmt::skip]
+        let filtered = [
+            1, 0x12, 0x34, 0x00, 0x01, 0xFF, 0xFF,
+        ];
+
+        let png = testing_png(ihdr_bytes(3, 1, 16, 0), &filtered, 1);
+        let image = Image::decode(&png, &Limits::default()).unwrap();
+
+        // Sub looks back two bytes for 16-bit grayscale.
+        assert_eq!(image.pixels(), [0x12, 0x34, 0x12, 0x35, 0x11, 0x34]);
+    }
+
+    #[test]
+    fn test_decode_truncated() {
+        let png = testing_png(ihdr_bytes(4, 4, 8, 0), &[0, 1, 2, 3, 4], 1);
+        assert!(Image::decode(&png, &Limits::default()).is_err());
+    }
+
+    #[test]
+    fn test_decode_limits() {
+        let png = testing_png(ihdr_bytes(4, 4, 8, 0), &[0; 20], 1);
+        let limits = Limits {
+            max_decompressed_size: 19,
+            ..Limits::default()
+        };
+
+        assert!(Image::decode(&png, &limits).is_err());
+    }
+
+    #[test]
+    fn test_decode_interlaced() {
+        let mut ihdr = Ihdr::try_from(ihdr_bytes(7, 5, 4, 0).as_slice()).unwrap();
+        ihdr.interlace_method = 1;
+
+        #[rustfmt::skip]
+        let pixels = vec![
+            0x01, 0x23, 0x45, 0x60,
+            0x89, 0xAB, 0xCD, 0xE0,
+            0xFE, 0xDC, 0xBA, 0x90,
+            0x76, 0x54, 0x32, 0x10,
+            0x11, 0x22, 0x33, 0x40,
+        ];
+
+        let filtered: Vec<u8> = interlace::interlace(&ihdr, &pixels)
+            .unwrap()
+            .iter()
+            .zip(ihdr.passes())
+            .filter(|(_, (width, height))| *width > 0 && *height > 0)
+            .flat_map(|(pass, (width, _))| {
+                let stride = ihdr.stride_for(width).unwrap();
+                pass.chunks(stride)
+                    .flat_map(|row| std::iter::once(0).chain(row.iter().copied()))
+                    .collect::<Vec<u8>>()
+            })
+            .collect();
+
+        let mut bytes = ihdr_bytes(7, 5, 4, 0);
+        bytes[12] = 1;
+
+        let png = testing_png(bytes, &filtered, 2);
+        let image = Image::decode(&png, &Limits::default()).unwrap();
+
+        assert_eq!(image.stride(), 4);
+        assert_eq!(image.pixels(), pixels);
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let strategies = [
+            FilterStrategy::Fixed(FilterType::None),
+            FilterStrategy::Fixed(FilterType::Paeth),
+            FilterStrategy::MinSum,
+            FilterStrategy::BruteForce,
+        ];
+
+        for filter in strategies {
+            let mut png = Png::try_from(crate::png::tests::PNG_FILE.as_slice()).unwrap();
+            let image = Image::decode(&png, &Limits::default()).unwrap();
+            let options = EncodeOptions {
+                filter,
+                idat_size: 1000,
+                ..EncodeOptions::default()
+            };
+
+            image.encode_into(&mut png, &options).unwrap();
+
+            let types: Vec<String> = png
+                .chunks()
+                .iter()
+                .map(|c| c.chunk_type().to_string())
+                .collect();
+            assert_eq!(&types[..4], ["IHDR", "sRGB", "gAMA", "pHYs"]);
+            assert_eq!(&types[types.len() - 2..], ["RuSt", "IEND"]);
+            assert!(png.chunks_by_type("IDAT").all(|c| c.data().len() <= 1000));
+
+            let bytes = png.as_bytes();
+            let reparsed = Png::try_from(bytes.as_slice()).unwrap();
+            assert_eq!(Image::decode(&reparsed, &Limits::default()).unwrap(), image);
+        }
+    }
+
+    #[test]
+    fn test_encode_interlaced_round_trip() {
+        let mut ihdr = Ihdr::try_from(ihdr_bytes(11, 6, 2, 0).as_slice()).unwrap();
+        ihdr.interlace_method = 1;
+
+        let pixels: Vec<u8> = (0..18u8)
+            .map(|i| i.wrapping_mul(73))
+            .enumerate()
+            .map(|(i, b)| if i % 3 == 2 { b & 0xC0 } else { b })
+            .collect();
+        let image = Image::new(ihdr, pixels).unwrap();
+
+        let mut png = testing_png(ihdr_bytes(11, 6, 2, 0), &[0; 18], 1);
+        image
+            .encode_into(&mut png, &EncodeOptions::default())
+            .unwrap();
+
+        assert_eq!(png.chunk_by_type("IHDR").unwrap().data()[12], 1);
+        assert_eq!(Image::decode(&png, &Limits::default()).unwrap(), image);
+    }
+
+    #[test]
+    fn test_new_image_wrong_size() {
+        let ihdr = Ihdr::try_from(ihdr_bytes(4, 4, 8, 0).as_slice()).unwrap();
+        assert!(Image::new(ihdr, vec![0; 15]).is_err());
+    }
+}
@@ -0,0 +1,195 @@
+use crate::image::Ihdr;
+
+/// Starting column, starting row, column spacing and row spacing of the seven Adam7 passes.
+const ADAM7: [(u32, u32, u32, u32); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+/// Width and height of each Adam7 pass of a `width` by `height` image.
+///
+/// Passes of small images can be empty, in which case they contribute no scanlines at all.
+pub fn pass_dimensions(width: u32, height: u32) -> [(u32, u32); 7] {
+    ADAM7.map(|(x0, y0, dx, dy)| {
+        (
+            width.saturating_sub(x0).div_ceil(dx),
+            height.saturating_sub(y0).div_ceil(dy),
+        )
+    })
+}
+
+/// Copies one pixel of `bits` bits between two packed buffers.
+///
+/// Pixels of fewer than 8 bits are packed from the most significant bit down, as in PNG
+/// scanlines, and larger pixels are always a whole number of bytes.
+fn copy_pixel(src: &[u8], src_index: usize, dst: &mut [u8], dst_index: usize, bits: usize) {
+    if bits >= 8 {
+        let bytes = bits / 8;
+        dst[dst_index * bytes..(dst_index + 1) * bytes]
+            .copy_from_slice(&src[src_index * bytes..(src_index + 1) * bytes]);
+        return;
+    }
+
+    let mask = (1u8 << bits) - 1;
+    let src_shift = 8 - bits - (src_index * bits) % 8;
+    let dst_shift = 8 - bits - (dst_index * bits) % 8;
+    let value = (src[src_index * bits / 8] >> src_shift) & mask;
+
+    let dst_byte = &mut dst[dst_index * bits / 8];
+    *dst_byte = (*dst_byte & !(mask << dst_shift)) | (value << dst_shift);
+}
+
+/// Scatters the unfiltered scanlines of every pass into a full-resolution pixel buffer.
+pub fn deinterlace(ihdr: &Ihdr, passes: &[Vec<u8>]) -> crate::Result<Vec<u8>> {
+    let bits = ihdr.bits_per_pixel();
+    let stride = ihdr.stride()?;
+    let mut pixels = vec![0; stride * ihdr.height as usize];
+
+    let dimensions = pass_dimensions(ihdr.width, ihdr.height);
+    for ((pass, &(width, height)), &(x0, y0, dx, dy)) in passes.iter().zip(&dimensions).zip(&ADAM7)
+    {
+        let pass_stride = ihdr.stride_for(width)?;
+        if pass.len() != pass_stride * height as usize {
+            return Err("interlaced pass has the wrong size".into());
+        }
+
+        for y in 0..height {
+            let src = &pass[y as usize * pass_stride..][..pass_stride];
+            let dst_y = (y0 + y * dy) as usize;
+            let dst = &mut pixels[dst_y * stride..][..stride];
+
+            for x in 0..width {
+                copy_pixel(src, x as usize, dst, (x0 + x * dx) as usize, bits);
+            }
+        }
+    }
+
+    Ok(pixels)
+}
+
+/// Splits a full-resolution pixel buffer into the unfiltered scanlines of each Adam7 pass.
+pub fn interlace(ihdr: &Ihdr, pixels: &[u8]) -> crate::Result<Vec<Vec<u8>>> {
+    let bits = ihdr.bits_per_pixel();
+    let stride = ihdr.stride()?;
+    if pixels.len() != stride * ihdr.height as usize {
+        return Err("pixel buffer does not match the image dimensions".into());
+    }
+
+    pass_dimensions(ihdr.width, ihdr.height)
+        .iter()
+        .zip(&ADAM7)
+        .map(|(&(width, height), &(x0, y0, dx, dy))| {
+            let pass_stride = ihdr.stride_for(width)?;
+            let mut pass = vec![0; pass_stride * height as usize];
+
+            for y in 0..height {
+                let src_y = (y0 + y * dy) as usize;
+                let src = &pixels[src_y * stride..][..stride];
+                let dst = &mut pass[y as usize * pass_stride..][..pass_stride];
+
+                for x in 0..width {
+                    copy_pixel(src, (x0 + x * dx) as usize, dst, x as usize, bits);
+                }
+            }
+
+            Ok(pass)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::ColorType;
+
+    fn testing_ihdr(width: u32, height: u32, bit_depth: u8, color_type: ColorType) -> Ihdr {
+        Ihdr {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            interlace_method: 1,
+        }
+    }
+
+    fn testing_pixels(ihdr: &Ihdr) -> Vec<u8> {
+        let len = ihdr.stride().unwrap() * ihdr.height as usize;
+        let mut pixels: Vec<u8> = (0..len).map(|i| (i * 37 % 256) as u8).collect();
+
+        // Clear the padding bits at the end of each row, which interlacing doesn't preserve.
+        let stride = ihdr.stride().unwrap();
+        let used_bits = ihdr.width as usize * ihdr.bits_per_pixel();
+        if !used_bits.is_multiple_of(8) {
+            for row in pixels.chunks_exact_mut(stride) {
+                row[stride - 1] &= 0xFF << (8 - used_bits % 8);
+            }
+        }
+
+        pixels
+    }
+
+    #[test]
+    fn test_pass_dimensions() {
+        assert_eq!(
+            pass_dimensions(8, 8),
+            [(1, 1), (1, 1), (2, 1), (2, 2), (4, 2), (4, 4), (8, 4)]
+        );
+        assert_eq!(
+            pass_dimensions(1, 1),
+            [(1, 1), (0, 1), (1, 0), (0, 1), (1, 0), (0, 1), (1, 0)]
+        );
+        assert_eq!(
+            pass_dimensions(5, 3),
+            [(1, 1), (1, 1), (2, 0), (1, 1), (3, 1), (2, 2), (5, 1)]
+        );
+    }
+
+    #[test]
+    fn test_interlace_passes() {
+        let ihdr = testing_ihdr(8, 8, 8, ColorType::Grayscale);
+        let pixels: Vec<u8> = (0..64).collect();
+        let passes = interlace(&ihdr, &pixels).unwrap();
+
+        assert_eq!(passes[0], [0]);
+        assert_eq!(passes[1], [4]);
+        assert_eq!(passes[2], [32, 36]);
+        assert_eq!(passes[3], [2, 6, 34, 38]);
+        assert_eq!(passes[6].len(), 32);
+        assert_eq!(&passes[6][..8], &[8, 9, 10, 11, 12, 13, 14, 15]);
+    }
+
+    #[test]
+    fn test_interlace_round_trip() {
+        let cases = [
+            (8, 8, 8, ColorType::Grayscale),
+            (13, 7, 1, ColorType::Grayscale),
+            (9, 11, 2, ColorType::Indexed),
+            (3, 17, 4, ColorType::Grayscale),
+            (7, 5, 8, ColorType::Rgb),
+            (6, 9, 16, ColorType::Rgba),
+            (1, 1, 16, ColorType::GrayscaleAlpha),
+        ];
+
+        for (width, height, bit_depth, color_type) in cases {
+            let ihdr = testing_ihdr(width, height, bit_depth, color_type);
+            let pixels = testing_pixels(&ihdr);
+
+            let passes = interlace(&ihdr, &pixels).unwrap();
+            assert_eq!(deinterlace(&ihdr, &passes).unwrap(), pixels);
+        }
+    }
+
+    #[test]
+    fn test_deinterlace_wrong_size() {
+        let ihdr = testing_ihdr(8, 8, 8, ColorType::Grayscale);
+        let mut passes = interlace(&ihdr, &[0; 64]).unwrap();
+        passes[3].pop();
+
+        assert!(deinterlace(&ihdr, &passes).is_err());
+    }
+}
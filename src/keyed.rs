@@ -0,0 +1,83 @@
+use rand_chacha::{
+    rand_core::{RngCore, SeedableRng},
+    ChaCha20Rng,
+};
+use sha2::Sha256;
+
+/// Fixed salt for key derivation. Nothing can be stored alongside a hidden payload without
+/// giving it away, so the salt only separates our keys from other uses of the same passphrase.
+const SALT: &[u8] = b"secretpng keyed traversal v1";
+
+/// PBKDF2 rounds, which make guessing passphrases expensive without slowing down normal use.
+/// Encryption derives its keys with the same number.
+pub const ROUNDS: u32 = 100_000;
+
+/// Creates a ChaCha20 generator seeded from `passphrase` and `purpose`.
+///
+/// The same inputs always produce the same sequence, so the encoder and decoder agree on it
+/// without exchanging anything but the passphrase. `purpose` keeps sequences used for different
+/// things independent of each other.
+pub fn rng(passphrase: &str, purpose: &str) -> ChaCha20Rng {
+    let salt = [SALT, purpose.as_bytes()].concat();
+    let mut seed = [0; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), &salt, ROUNDS, &mut seed);
+
+    ChaCha20Rng::from_seed(seed)
+}
+
+/// Returns a uniformly distributed number below `bound`, which must not be zero.
+fn below(rng: &mut ChaCha20Rng, bound: u64) -> u64 {
+    // Reject the top end of the range that would make some results more likely than others.
+    let zone = u64::MAX - (u64::MAX - bound + 1) % bound;
+    loop {
+        let value = rng.next_u64();
+        if value <= zone {
+            return value % bound;
+        }
+    }
+}
+
+/// Shuffles `items` with a Fisher-Yates shuffle driven by `rng`.
+pub fn shuffle<T>(items: &mut [T], rng: &mut ChaCha20Rng) {
+    for i in (1..items.len()).rev() {
+        let j = below(rng, i as u64 + 1) as usize;
+        items.swap(i, j);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rng_is_deterministic() {
+        let a = rng("correct horse", "test").next_u64();
+        let b = rng("correct horse", "test").next_u64();
+
+        assert_eq!(a, b);
+        assert_ne!(a, rng("battery staple", "test").next_u64());
+        assert_ne!(a, rng("correct horse", "other").next_u64());
+    }
+
+    #[test]
+    fn test_shuffle_is_permutation() {
+        let mut items: Vec<usize> = (0..1000).collect();
+        shuffle(&mut items, &mut rng("key", "test"));
+
+        assert_ne!(items, (0..1000).collect::<Vec<_>>());
+
+        items.sort_unstable();
+        assert_eq!(items, (0..1000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_below() {
+        let mut rng = rng("key", "test");
+
+        for bound in [1, 2, 3, 7, 1000, u64::MAX] {
+            for _ in 0..100 {
+                assert!(below(&mut rng, bound) < bound);
+            }
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+/// Upper bounds applied while parsing PNG files that may come from an untrusted source.
+///
+/// Every limit is checked before the corresponding memory is allocated, so exceeding one results
+/// in a `LimitError` instead of an out-of-memory abort.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Largest length field accepted for a single chunk.
+    pub max_chunk_length: u32,
+    /// Largest number of chunks accepted in a single file.
+    pub max_chunks: usize,
+    /// Largest size a single zlib stream (a zTXt chunk or the joined IDAT data) may inflate to.
+    pub max_decompressed_size: usize,
+    /// Largest file accepted, including the PNG signature.
+    pub max_file_size: u64,
+}
+
+impl Limits {
+    /// Limits that only enforce what the PNG specification itself requires.
+    pub const UNLIMITED: Limits = Limits {
+        max_chunk_length: i32::MAX as u32,
+        max_chunks: usize::MAX,
+        max_decompressed_size: usize::MAX,
+        max_file_size: u64::MAX,
+    };
+
+    pub fn check_chunk_length(&self, length: u32) -> Result<(), LimitError> {
+        check(
+            LimitKind::ChunkLength,
+            length as u64,
+            self.max_chunk_length as u64,
+        )
+    }
+
+    pub fn check_chunks(&self, count: usize) -> Result<(), LimitError> {
+        check(LimitKind::Chunks, count as u64, self.max_chunks as u64)
+    }
+
+    pub fn check_decompressed_size(&self, size: usize) -> Result<(), LimitError> {
+        check(
+            LimitKind::DecompressedSize,
+            size as u64,
+            self.max_decompressed_size as u64,
+        )
+    }
+
+    pub fn check_file_size(&self, size: u64) -> Result<(), LimitError> {
+        check(LimitKind::FileSize, size, self.max_file_size)
+    }
+}
+
+impl Default for Limits {
+    /// Generous limits that still keep a hostile file from exhausting memory.
+    fn default() -> Self {
+        Self {
+            max_chunk_length: 256 * 1024 * 1024,
+            max_chunks: 1 << 20,
+            max_decompressed_size: 512 * 1024 * 1024,
+            max_file_size: 4 * 1024 * 1024 * 1024,
+        }
+    }
+}
+
+fn check(kind: LimitKind, actual: u64, limit: u64) -> Result<(), LimitError> {
+    if actual > limit {
+        return Err(LimitError {
+            kind,
+            actual: Some(actual),
+            limit,
+        });
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitKind {
+    ChunkLength,
+    Chunks,
+    DecompressedSize,
+    FileSize,
+}
+
+impl Display for LimitKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LimitKind::ChunkLength => "chunk length",
+            LimitKind::Chunks => "chunk count",
+            LimitKind::DecompressedSize => "decompressed size",
+            LimitKind::FileSize => "file size",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// Returned when a file exceeds one of the configured `Limits`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LimitError {
+    kind: LimitKind,
+    actual: Option<u64>,
+    limit: u64,
+}
+
+impl LimitError {
+    /// Creates an error for input that went over `limit` by an unknown amount.
+    pub fn exceeded(kind: LimitKind, limit: u64) -> Self {
+        Self {
+            kind,
+            actual: None,
+            limit,
+        }
+    }
+
+    pub fn kind(&self) -> LimitKind {
+        self.kind
+    }
+}
+
+impl Display for LimitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.actual {
+            Some(actual) => write!(
+                f,
+                "{} of {} exceeds the limit of {}",
+                self.kind, actual, self.limit
+            ),
+            None => write!(f, "{} exceeds the limit of {}", self.kind, self.limit),
+        }
+    }
+}
+
+impl Error for LimitError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_within_limits() {
+        let limits = Limits::default();
+
+        assert!(limits.check_chunk_length(1024).is_ok());
+        assert!(limits.check_chunks(10).is_ok());
+        assert!(limits.check_decompressed_size(1024).is_ok());
+        assert!(limits.check_file_size(1024).is_ok());
+    }
+
+    #[test]
+    fn test_exceeds_limit() {
+        let limits = Limits {
+            max_chunk_length: 16,
+            ..Limits::default()
+        };
+
+        let err = limits.check_chunk_length(0x7FFF_FFFF).unwrap_err();
+        assert_eq!(err.kind(), LimitKind::ChunkLength);
+        assert_eq!(
+            err.to_string(),
+            "chunk length of 2147483647 exceeds the limit of 16"
+        );
+    }
+
+    #[test]
+    fn test_unlimited_enforces_spec_maximum() {
+        assert!(Limits::UNLIMITED.check_chunk_length(0x7FFF_FFFF).is_ok());
+        assert!(Limits::UNLIMITED.check_chunk_length(0x8000_0000).is_err());
+    }
+}
@@ -0,0 +1,711 @@
+use crate::{
+    image::{ColorType, Image},
+    keyed,
+};
+use std::str::FromStr;
+
+/// Which channels of each pixel carry payload bits.
+///
+/// Grayscale images have a single gray channel, which is used if any of red, green or blue is
+/// selected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelMask {
+    pub red: bool,
+    pub green: bool,
+    pub blue: bool,
+    pub alpha: bool,
+}
+
+impl ChannelMask {
+    /// Whether each channel of a pixel of `color_type` is selected, in sample order.
+    fn selected(&self, color_type: ColorType) -> crate::Result<Vec<bool>> {
+        let gray = self.red || self.green || self.blue;
+
+        match color_type {
+            ColorType::Grayscale => Ok(vec![gray]),
+            ColorType::GrayscaleAlpha => Ok(vec![gray, self.alpha]),
+            ColorType::Rgb => Ok(vec![self.red, self.green, self.blue]),
+            ColorType::Rgba => Ok(vec![self.red, self.green, self.blue, self.alpha]),
+            ColorType::Indexed => {
+                Err("LSB embedding is not supported for indexed-color images".into())
+            }
+        }
+    }
+}
+
+impl Default for ChannelMask {
+    fn default() -> Self {
+        Self {
+            red: true,
+            green: true,
+            blue: true,
+            alpha: false,
+        }
+    }
+}
+
+impl FromStr for ChannelMask {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        let mut mask = Self {
+            red: false,
+            green: false,
+            blue: false,
+            alpha: false,
+        };
+
+        for c in s.chars() {
+            match c.to_ascii_lowercase() {
+                'r' => mask.red = true,
+                'g' => mask.green = true,
+                'b' => mask.blue = true,
+                'a' => mask.alpha = true,
+                _ => return Err(format!("unknown channel '{c}', expected r, g, b or a").into()),
+            }
+        }
+
+        if !(mask.red || mask.green || mask.blue || mask.alpha) {
+            return Err("at least one channel must be selected".into());
+        }
+
+        Ok(mask)
+    }
+}
+
+/// Settings shared by LSB embedding and extraction, which must match for a payload to be found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LsbOptions {
+    /// Number of low bits of each selected sample that carry payload bits.
+    pub bits: u8,
+    pub channels: ChannelMask,
+    /// Scatters the payload over samples in an order derived from this passphrase instead of
+    /// filling them from the top-left corner.
+    pub passphrase: Option<String>,
+    /// Hides `k` bits in every block of `2^k - 1` samples with Hamming syndrome coding, changing
+    /// at most one sample per block. Only the lowest bit of each sample is used.
+    pub matrix: Option<u8>,
+}
+
+impl Default for LsbOptions {
+    fn default() -> Self {
+        Self {
+            bits: 1,
+            channels: ChannelMask::default(),
+            passphrase: None,
+            matrix: None,
+        }
+    }
+}
+
+impl LsbOptions {
+    /// Number of payload bits the given number of samples can carry.
+    fn bit_capacity(&self, samples: usize) -> usize {
+        match self.matrix {
+            Some(k) => samples / block_len(k) * k as usize,
+            None => samples * self.bits as usize,
+        }
+    }
+}
+
+/// Largest number of bits per block accepted for matrix embedding.
+const MAX_MATRIX_BITS: u8 = 16;
+
+/// Number of samples in a matrix embedding block that carries `k` bits.
+fn block_len(k: u8) -> usize {
+    (1 << k) - 1
+}
+
+/// What embedding a payload did to the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmbedReport {
+    /// Payload bits written, including the length prefix.
+    pub bits: usize,
+    /// Samples that were written to.
+    pub samples: usize,
+    /// Samples whose value actually changed.
+    pub modified: usize,
+}
+
+impl EmbedReport {
+    /// Average number of payload bits hidden per modified sample, or `None` if the payload
+    /// already matched the cover and nothing had to change.
+    pub fn efficiency(&self) -> Option<f64> {
+        (self.modified > 0).then(|| self.bits as f64 / self.modified as f64)
+    }
+}
+
+/// Number of bytes used to store the payload length in front of the payload.
+pub const LENGTH_PREFIX: usize = 4;
+
+/// Location of one sample in a packed pixel buffer: the byte holding its least significant bit
+/// and how far that bit is shifted up within the byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sample {
+    byte: usize,
+    shift: u8,
+}
+
+/// Lists every selected sample of `image` in the order payload bits are written to them.
+///
+/// That is row-major order, or a permutation of it derived from the passphrase if there is one.
+pub fn samples(image: &Image, options: &LsbOptions) -> crate::Result<Vec<Sample>> {
+    let selected = options.channels.selected(image.ihdr().color_type)?;
+    positions(image, &selected, options)
+}
+
+/// Lists the palette index of every pixel of an indexed-color image as a sample, in the same
+/// order as `samples`.
+///
+/// Changing the low bits of an index picks a neighboring palette entry, so the palette should be
+/// sorted first for this to go unnoticed. Only indices whose every low-bit variant is below
+/// `palette_len` are used, so embedding never points a pixel past the end of the palette. Since
+/// embedding only changes those low bits, the same samples are found again when extracting.
+pub fn index_samples(
+    image: &Image,
+    options: &LsbOptions,
+    palette_len: usize,
+) -> crate::Result<Vec<Sample>> {
+    if image.ihdr().color_type != ColorType::Indexed {
+        return Err("only indexed-color images have palette indices".into());
+    }
+
+    let bit_depth = image.ihdr().bit_depth;
+    let low_bits = (1usize << options.bits.min(bit_depth)) - 1;
+    let pixels = image.pixels();
+
+    let mut samples = positions(image, &[true], options)?;
+    samples.retain(|&sample| {
+        let index = usize::from(read_bits(pixels, sample, bit_depth));
+        index | low_bits < palette_len
+    });
+
+    Ok(samples)
+}
+
+/// Locates the samples of the channels marked in `selected`, which has one flag per channel.
+fn positions(image: &Image, selected: &[bool], options: &LsbOptions) -> crate::Result<Vec<Sample>> {
+    let ihdr = image.ihdr();
+    let bit_depth = ihdr.bit_depth as usize;
+
+    if options.bits == 0 || options.bits as usize > bit_depth.min(8) {
+        return Err(format!(
+            "bits per channel must be between 1 and {} for this image",
+            bit_depth.min(8)
+        )
+        .into());
+    }
+
+    if let Some(k) = options.matrix {
+        if k == 0 || k > MAX_MATRIX_BITS {
+            return Err(
+                format!("matrix embedding needs 1 to {MAX_MATRIX_BITS} bits per block").into(),
+            );
+        }
+        if options.bits != 1 {
+            return Err("matrix embedding uses one bit per sample".into());
+        }
+    }
+
+    let mut samples = Vec::new();
+
+    for y in 0..image.height() as usize {
+        let row = y * image.stride();
+        for x in 0..image.width() as usize {
+            for (channel, _) in selected.iter().enumerate().filter(|(_, s)| **s) {
+                let bit = (x * selected.len() + channel) * bit_depth;
+                let last_bit = bit + bit_depth - 1;
+
+                samples.push(Sample {
+                    byte: row + last_bit / 8,
+                    shift: (7 - last_bit % 8) as u8,
+                });
+            }
+        }
+    }
+
+    if let Some(passphrase) = &options.passphrase {
+        keyed::shuffle(&mut samples, &mut keyed::rng(passphrase, "lsb"));
+    }
+
+    Ok(samples)
+}
+
+/// Number of payload bytes that fit in the image with these options.
+pub fn capacity(image: &Image, options: &LsbOptions) -> crate::Result<usize> {
+    Ok(capacity_of(&samples(image, options)?, options))
+}
+
+/// Number of payload bytes that fit in the palette indices of an indexed-color image with a
+/// palette of `palette_len` entries.
+pub fn index_capacity(
+    image: &Image,
+    options: &LsbOptions,
+    palette_len: usize,
+) -> crate::Result<usize> {
+    Ok(capacity_of(
+        &index_samples(image, options, palette_len)?,
+        options,
+    ))
+}
+
+fn capacity_of(samples: &[Sample], options: &LsbOptions) -> usize {
+    (options.bit_capacity(samples.len()) / 8).saturating_sub(LENGTH_PREFIX)
+}
+
+fn read_bits(pixels: &[u8], sample: Sample, bits: u8) -> u8 {
+    (pixels[sample.byte] >> sample.shift) & ((1u16 << bits) - 1) as u8
+}
+
+fn write_bits(pixels: &mut [u8], sample: Sample, bits: u8, value: u8) {
+    let mask = (((1u16 << bits) - 1) as u8) << sample.shift;
+    let byte = &mut pixels[sample.byte];
+    *byte = (*byte & !mask) | ((value << sample.shift) & mask);
+}
+
+/// Iterates over the bits of `bytes`, most significant bit first.
+pub fn bits_of(bytes: &[u8]) -> impl Iterator<Item = u8> + '_ {
+    bytes
+        .iter()
+        .flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1))
+}
+
+/// Syndrome of a block of samples: the XOR of the one-based positions of every sample whose
+/// lowest bit is set.
+fn syndrome(pixels: &[u8], block: &[Sample]) -> usize {
+    block
+        .iter()
+        .enumerate()
+        .filter(|(_, &sample)| read_bits(pixels, sample, 1) == 1)
+        .fold(0, |syndrome, (i, _)| syndrome ^ (i + 1))
+}
+
+/// Hides `payload` in the low bits of the selected samples, prefixed with its length.
+pub fn embed(
+    image: &mut Image,
+    payload: &[u8],
+    options: &LsbOptions,
+) -> crate::Result<EmbedReport> {
+    let samples = samples(image, options)?;
+    embed_in(image.pixels_mut(), &samples, payload, options)
+}
+
+/// Hides `payload` in the low bits of the palette indices of an indexed-color image with a
+/// palette of `palette_len` entries.
+pub fn embed_indices(
+    image: &mut Image,
+    payload: &[u8],
+    options: &LsbOptions,
+    palette_len: usize,
+) -> crate::Result<EmbedReport> {
+    let samples = index_samples(image, options, palette_len)?;
+    embed_in(image.pixels_mut(), &samples, payload, options)
+}
+
+fn embed_in(
+    pixels: &mut [u8],
+    samples: &[Sample],
+    payload: &[u8],
+    options: &LsbOptions,
+) -> crate::Result<EmbedReport> {
+    let available = options.bit_capacity(samples.len()) / 8;
+
+    if payload.len() + LENGTH_PREFIX > available {
+        return Err(format!(
+            "payload of {} bytes does not fit, the image can hold {} bytes",
+            payload.len(),
+            available.saturating_sub(LENGTH_PREFIX)
+        )
+        .into());
+    }
+
+    let length = u32::try_from(payload.len())?.to_be_bytes();
+    let mut bits = bits_of(&length).chain(bits_of(payload));
+    let mut report = EmbedReport {
+        bits: (LENGTH_PREFIX + payload.len()) * 8,
+        samples: 0,
+        modified: 0,
+    };
+
+    if let Some(k) = options.matrix {
+        for block in samples.chunks_exact(block_len(k)) {
+            let mut value = 0;
+            let mut count = 0;
+            for bit in bits.by_ref().take(k as usize) {
+                value = (value << 1) | bit as usize;
+                count += 1;
+            }
+
+            if count == 0 {
+                break;
+            }
+
+            // A partial group at the very end matches the block's current low syndrome bits.
+            let syndrome = syndrome(pixels, block);
+            let keep = k as usize - count;
+            let value = (value << keep) | (syndrome & ((1 << keep) - 1));
+
+            report.samples += block.len();
+            if value != syndrome {
+                let sample = block[(value ^ syndrome) - 1];
+                write_bits(pixels, sample, 1, read_bits(pixels, sample, 1) ^ 1);
+                report.modified += 1;
+            }
+        }
+
+        return Ok(report);
+    }
+
+    for &sample in samples {
+        let mut value = 0;
+        let mut count = 0;
+        for bit in bits.by_ref().take(options.bits as usize) {
+            value = (value << 1) | bit;
+            count += 1;
+        }
+
+        if count == 0 {
+            break;
+        }
+
+        // A partial group at the very end keeps the sample's remaining low bits untouched.
+        let keep = options.bits - count;
+        let current = read_bits(pixels, sample, options.bits);
+        let value = (value << keep) | (current & ((1 << keep) - 1));
+        write_bits(pixels, sample, options.bits, value);
+
+        report.samples += 1;
+        if value != current {
+            report.modified += 1;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Reads back a payload hidden by `embed` with the same options.
+pub fn extract(image: &Image, options: &LsbOptions) -> crate::Result<Vec<u8>> {
+    extract_from(image.pixels(), &samples(image, options)?, options)
+}
+
+/// Reads back a payload hidden by `embed_indices` with the same options.
+pub fn extract_indices(
+    image: &Image,
+    options: &LsbOptions,
+    palette_len: usize,
+) -> crate::Result<Vec<u8>> {
+    extract_from(
+        image.pixels(),
+        &index_samples(image, options, palette_len)?,
+        options,
+    )
+}
+
+fn extract_from(pixels: &[u8], samples: &[Sample], options: &LsbOptions) -> crate::Result<Vec<u8>> {
+    let mut bits: Box<dyn Iterator<Item = u8>> = match options.matrix {
+        Some(k) => Box::new(samples.chunks_exact(block_len(k)).flat_map(move |block| {
+            let value = syndrome(pixels, block);
+            (0..k).rev().map(move |i| ((value >> i) & 1) as u8)
+        })),
+        None => Box::new(samples.iter().flat_map(|&sample| {
+            let value = read_bits(pixels, sample, options.bits);
+            (0..options.bits).rev().map(move |i| (value >> i) & 1)
+        })),
+    };
+
+    read_payload(
+        &mut bits,
+        options.bit_capacity(samples.len()) / 8,
+        "image is too small to hold a payload",
+        "no LSB payload found with these settings",
+    )
+}
+
+/// Reads a payload prefixed with its length from `bits`, most significant bit first.
+///
+/// `capacity` is how many bytes the carrier holds including the prefix, so longer lengths mean
+/// there is no payload. `too_small` is the error if not even the prefix can be read, and
+/// `not_found` the error for a length that doesn't fit.
+pub fn read_payload(
+    bits: &mut impl Iterator<Item = u8>,
+    capacity: usize,
+    too_small: &str,
+    not_found: &str,
+) -> crate::Result<Vec<u8>> {
+    let mut next_byte = || (0..8).try_fold(0u8, |byte, _| bits.next().map(|bit| (byte << 1) | bit));
+
+    let mut length = [0; LENGTH_PREFIX];
+    for byte in length.iter_mut() {
+        *byte = next_byte().ok_or(too_small)?;
+    }
+
+    let length = u32::from_be_bytes(length) as usize;
+    if length > capacity.saturating_sub(LENGTH_PREFIX) {
+        return Err(not_found.into());
+    }
+
+    (0..length)
+        .map(|_| next_byte().ok_or_else(|| "payload is truncated".into()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        image::Ihdr,
+        png::{Limits, Png},
+    };
+
+    fn testing_image() -> Image {
+        let png = Png::try_from(crate::png::tests::PNG_FILE.as_slice()).unwrap();
+        Image::decode(&png, &Limits::default()).unwrap()
+    }
+
+    fn gray_image(width: u32, height: u32, bit_depth: u8) -> Image {
+        let ihdr = Ihdr {
+            width,
+            height,
+            bit_depth,
+            color_type: ColorType::Grayscale,
+            interlace_method: 0,
+        };
+        let len = ihdr.stride().unwrap() * height as usize;
+        Image::new(ihdr, (0..len).map(|i| (i * 91 % 256) as u8).collect()).unwrap()
+    }
+
+    #[test]
+    fn test_channel_mask_from_str() {
+        let mask = ChannelMask::from_str("RgA").unwrap();
+        assert!(mask.red && mask.green && !mask.blue && mask.alpha);
+
+        assert!(ChannelMask::from_str("").is_err());
+        assert!(ChannelMask::from_str("rgbx").is_err());
+    }
+
+    #[test]
+    fn test_capacity() {
+        let image = testing_image();
+
+        // 50x50 pixels with three channels at one bit each, minus the length prefix.
+        assert_eq!(capacity(&image, &LsbOptions::default()).unwrap(), 933);
+
+        let options = LsbOptions {
+            bits: 2,
+            channels: ChannelMask::from_str("rgba").unwrap(),
+            ..LsbOptions::default()
+        };
+        assert_eq!(capacity(&image, &options).unwrap(), 2496);
+    }
+
+    #[test]
+    fn test_embed_extract() {
+        let mut image = testing_image();
+        let original = image.clone();
+        let options = LsbOptions::default();
+
+        embed(
+            &mut image,
+            b"This is where your secret message will be!",
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!(
+            extract(&image, &options).unwrap(),
+            b"This is where your secret message will be!"
+        );
+
+        // Only the lowest bit of the color channels may change.
+        for (i, (a, b)) in image.pixels().iter().zip(original.pixels()).enumerate() {
+            if i % 4 == 3 {
+                assert_eq!(a, b);
+            } else {
+                assert!(a ^ b <= 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_embed_extract_options() {
+        let cases = [
+            (gray_image(17, 9, 1), 1, "r"),
+            (gray_image(10, 10, 2), 2, "g"),
+            (gray_image(13, 7, 4), 3, "b"),
+            (gray_image(8, 8, 16), 8, "rgb"),
+        ];
+
+        for (mut image, bits, channels) in cases {
+            let options = LsbOptions {
+                bits,
+                channels: ChannelMask::from_str(channels).unwrap(),
+                ..LsbOptions::default()
+            };
+            let payload = vec![0xA5; capacity(&image, &options).unwrap()];
+
+            embed(&mut image, &payload, &options).unwrap();
+            assert_eq!(extract(&image, &options).unwrap(), payload);
+        }
+    }
+
+    #[test]
+    fn test_sixteen_bit_changes_low_byte() {
+        let mut image = gray_image(4, 1, 16);
+        let original = image.clone();
+
+        embed(&mut image, &[], &LsbOptions::default()).unwrap_err();
+        embed(
+            &mut image,
+            &[],
+            &LsbOptions {
+                bits: 8,
+                ..LsbOptions::default()
+            },
+        )
+        .unwrap();
+
+        for (i, (a, b)) in image.pixels().iter().zip(original.pixels()).enumerate() {
+            if i % 2 == 0 {
+                assert_eq!(a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_payload_too_large() {
+        let mut image = gray_image(8, 8, 8);
+        assert!(embed(&mut image, &[0; 5], &LsbOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_invalid_bits() {
+        let image = gray_image(8, 8, 2);
+        let options = LsbOptions {
+            bits: 3,
+            ..LsbOptions::default()
+        };
+
+        assert!(capacity(&image, &options).is_err());
+    }
+
+    #[test]
+    fn test_keyed_embed_extract() {
+        let mut image = testing_image();
+        let options = LsbOptions {
+            passphrase: Some(String::from("correct horse battery staple")),
+            ..LsbOptions::default()
+        };
+
+        embed(&mut image, b"scattered", &options).unwrap();
+        assert_eq!(extract(&image, &options).unwrap(), b"scattered");
+
+        // Without the passphrase the length prefix is read from the wrong samples.
+        let unkeyed = extract(&image, &LsbOptions::default());
+        assert!(unkeyed.map_or(true, |payload| payload != b"scattered"));
+
+        let wrong_key = LsbOptions {
+            passphrase: Some(String::from("wrong")),
+            ..LsbOptions::default()
+        };
+        let wrong = extract(&image, &wrong_key);
+        assert!(wrong.map_or(true, |payload| payload != b"scattered"));
+    }
+
+    #[test]
+    fn test_keyed_samples_are_scattered() {
+        let image = testing_image();
+        let options = LsbOptions {
+            passphrase: Some(String::from("key")),
+            ..LsbOptions::default()
+        };
+
+        let plain = samples(&image, &LsbOptions::default()).unwrap();
+        let keyed = samples(&image, &options).unwrap();
+
+        assert_ne!(plain[..32], keyed[..32]);
+        assert_eq!(plain.len(), keyed.len());
+    }
+
+    #[test]
+    fn test_matrix_embed_extract() {
+        for k in [1, 2, 3, 4, 7] {
+            let mut image = testing_image();
+            let options = LsbOptions {
+                matrix: Some(k),
+                ..LsbOptions::default()
+            };
+            let payload = vec![0x3C; capacity(&image, &options).unwrap()];
+
+            let report = embed(&mut image, &payload, &options).unwrap();
+            assert_eq!(extract(&image, &options).unwrap(), payload);
+            assert_eq!(report.bits, (payload.len() + LENGTH_PREFIX) * 8);
+
+            // At most one change per block of 2^k - 1 samples.
+            assert!(report.modified <= report.samples / block_len(k));
+        }
+    }
+
+    #[test]
+    fn test_matrix_changes_fewer_samples() {
+        let payload = b"Matrix embedding hides the same message with fewer changes.";
+
+        let mut plain = testing_image();
+        let plain_report = embed(&mut plain, payload, &LsbOptions::default()).unwrap();
+
+        let mut matrix = testing_image();
+        let options = LsbOptions {
+            matrix: Some(3),
+            ..LsbOptions::default()
+        };
+        let matrix_report = embed(&mut matrix, payload, &options).unwrap();
+
+        assert!(matrix_report.modified < plain_report.modified);
+        assert!(matrix_report.efficiency().unwrap() > plain_report.efficiency().unwrap());
+    }
+
+    #[test]
+    fn test_efficiency_without_changes() {
+        let mut image = testing_image();
+        embed(&mut image, b"already there", &LsbOptions::default()).unwrap();
+
+        // Embedding the same payload again has nothing left to change.
+        let report = embed(&mut image, b"already there", &LsbOptions::default()).unwrap();
+        assert_eq!(report.modified, 0);
+        assert_eq!(report.efficiency(), None);
+    }
+
+    #[test]
+    fn test_matrix_syndrome() {
+        let mut image = gray_image(7, 1, 8);
+        let options = LsbOptions {
+            matrix: Some(3),
+            ..LsbOptions::default()
+        };
+        let block = samples(&image, &options).unwrap();
+
+        for (i, &sample) in block.iter().enumerate() {
+            write_bits(image.pixels_mut(), sample, 1, (i == 2 || i == 4) as u8);
+        }
+
+        // Positions 3 and 5 are set, so the syndrome is 3 ^ 5.
+        assert_eq!(syndrome(image.pixels(), &block), 6);
+    }
+
+    #[test]
+    fn test_matrix_invalid() {
+        let image = gray_image(8, 8, 8);
+
+        let too_many_bits = LsbOptions {
+            bits: 2,
+            matrix: Some(3),
+            ..LsbOptions::default()
+        };
+        assert!(capacity(&image, &too_many_bits).is_err());
+
+        let zero = LsbOptions {
+            matrix: Some(0),
+            ..LsbOptions::default()
+        };
+        assert!(capacity(&image, &zero).is_err());
+    }
+}
@@ -1,23 +1,55 @@
 #![allow(dead_code)]
 
+mod analyze;
+mod apng;
+mod carve;
 mod chunk;
 mod chunk_type;
+mod cipher;
 mod cli;
+mod container;
+mod diff;
+mod fec;
+mod filter;
+mod format;
+mod frame_lsb;
+mod ico;
+mod image;
+mod interlace;
+mod keyed;
+mod limits;
+mod lsb;
+mod palette;
 mod png;
+mod slack;
+mod steganalysis;
+mod strip;
+mod text;
+mod transplant;
+mod zlib;
 
 type Error = Box<dyn std::error::Error>;
 type Result<T> = std::result::Result<T, Error>;
 
 use clap::Parser;
 use cli::{Cli, Subcommand};
+use limits::Limits;
 
 fn main() -> Result<()> {
     let args = Cli::parse();
+    let limits = Limits::from(&args.limits);
 
     match args.subcommand {
-        Subcommand::Encode(args) => cli::encode(args),
-        Subcommand::Decode(args) => cli::decode(args),
-        Subcommand::Remove(args) => cli::remove(args),
-        Subcommand::Print(args) => cli::print(args),
+        Subcommand::Encode(args) => cli::encode(args, &limits),
+        Subcommand::Decode(args) => cli::decode(args, &limits),
+        Subcommand::Remove(args) => cli::remove(args, &limits),
+        Subcommand::Print(args) => cli::print(args, &limits),
+        Subcommand::Validate(args) => cli::validate(args, &limits),
+        Subcommand::Analyze(args) => cli::analyze(args, &limits),
+        Subcommand::Frames(args) => cli::frames(args, &limits),
+        Subcommand::Carve(args) => cli::carve(args, &limits),
+        Subcommand::Diff(args) => cli::diff(args, &limits),
+        Subcommand::Strip(args) => cli::strip(args, &limits),
+        Subcommand::Transplant(args) => cli::transplant(args, &limits),
     }
 }
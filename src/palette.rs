@@ -0,0 +1,688 @@
+use crate::{
+    image::{ColorType, Image},
+    lsb::{self, LENGTH_PREFIX},
+    png::{Chunk, ChunkType, Png},
+};
+
+/// The palette of an indexed-color image together with the chunks that run parallel to it.
+///
+/// Reordering entries has to move their transparency and histogram along with them, and the
+/// background index has to follow its entry, so they are kept in one place and written back
+/// together.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    entries: Vec<[u8; 3]>,
+    /// Alpha of every entry, which is 255 for entries not covered by the tRNS chunk.
+    alpha: Vec<u8>,
+    has_trns: bool,
+    histogram: Option<Vec<u16>>,
+    /// Index of the entry the bKGD chunk picks as the background color.
+    background: Option<u8>,
+}
+
+impl Palette {
+    /// Reads the PLTE chunk and, if present, the tRNS, hIST and bKGD chunks of `png`.
+    pub fn from_png(png: &Png) -> crate::Result<Self> {
+        let plte = png
+            .chunk_by_type("PLTE")
+            .ok_or("indexed-color image has no PLTE chunk")?
+            .data();
+
+        if plte.is_empty() || plte.len() % 3 != 0 || plte.len() > 256 * 3 {
+            return Err("PLTE chunk must hold between 1 and 256 RGB entries".into());
+        }
+
+        let entries: Vec<[u8; 3]> = plte
+            .chunks_exact(3)
+            .map(|rgb| [rgb[0], rgb[1], rgb[2]])
+            .collect();
+
+        let trns = png.chunk_by_type("tRNS").map(Chunk::data);
+        let mut alpha = vec![255; entries.len()];
+        if let Some(trns) = trns {
+            if trns.len() > entries.len() {
+                return Err("tRNS chunk has more entries than the palette".into());
+            }
+            alpha[..trns.len()].copy_from_slice(trns);
+        }
+
+        let histogram = match png.chunk_by_type("hIST").map(Chunk::data) {
+            Some(hist) if hist.len() != entries.len() * 2 => {
+                return Err("hIST chunk does not match the palette".into());
+            }
+            Some(hist) => Some(
+                hist.chunks_exact(2)
+                    .map(|count| u16::from_be_bytes([count[0], count[1]]))
+                    .collect(),
+            ),
+            None => None,
+        };
+
+        let background = match png.chunk_by_type("bKGD").map(Chunk::data) {
+            Some(&[index]) if usize::from(index) < entries.len() => Some(index),
+            Some(_) => return Err("bKGD chunk does not refer to a palette entry".into()),
+            None => None,
+        };
+
+        Ok(Self {
+            entries,
+            alpha,
+            has_trns: trns.is_some(),
+            histogram,
+            background,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn entries(&self) -> &[[u8; 3]] {
+        &self.entries
+    }
+
+    pub fn alpha(&self) -> &[u8] {
+        &self.alpha
+    }
+
+    /// Replaces the PLTE, tRNS, hIST and bKGD chunks of `png` with this palette.
+    ///
+    /// The tRNS chunk is only as long as it needs to be, and is removed if every entry ended up
+    /// opaque.
+    pub fn write_into(&self, png: &mut Png) -> crate::Result<()> {
+        let plte = self.entries.concat();
+        png.replace_chunks(
+            "PLTE",
+            vec![Chunk::new(ChunkType::from_bytes(*b"PLTE"), plte)],
+        )?;
+
+        if self.has_trns {
+            let len = self
+                .alpha
+                .iter()
+                .rposition(|&a| a != 255)
+                .map_or(0, |i| i + 1);
+            if len == 0 {
+                png.remove_chunk("tRNS")?;
+            } else {
+                let trns = self.alpha[..len].to_vec();
+                png.replace_chunks(
+                    "tRNS",
+                    vec![Chunk::new(ChunkType::from_bytes(*b"tRNS"), trns)],
+                )?;
+            }
+        }
+
+        if let Some(histogram) = &self.histogram {
+            let hist = histogram
+                .iter()
+                .flat_map(|count| count.to_be_bytes())
+                .collect();
+            png.replace_chunks(
+                "hIST",
+                vec![Chunk::new(ChunkType::from_bytes(*b"hIST"), hist)],
+            )?;
+        }
+
+        if let Some(background) = self.background {
+            png.replace_chunks(
+                "bKGD",
+                vec![Chunk::new(
+                    ChunkType::from_bytes(*b"bKGD"),
+                    vec![background],
+                )],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Moves entry `order[i]` to position `i` and updates the pixels of `image` to match.
+    fn permute(&mut self, image: &mut Image, order: &[usize]) -> crate::Result<()> {
+        let mut map = vec![0; order.len()];
+        for (new, &old) in order.iter().enumerate() {
+            map[old] = new;
+        }
+
+        remap(image, &map)?;
+        self.entries = order.iter().map(|&i| self.entries[i]).collect();
+        self.alpha = order.iter().map(|&i| self.alpha[i]).collect();
+        if let Some(histogram) = &mut self.histogram {
+            *histogram = order.iter().map(|&i| histogram[i]).collect();
+        }
+        if let Some(background) = &mut self.background {
+            *background = u8::try_from(map[usize::from(*background)])?;
+        }
+
+        Ok(())
+    }
+
+    /// Key that orders entries canonically, covering everything that affects their appearance.
+    fn key(&self, index: usize) -> ([u8; 3], u8) {
+        (self.entries[index], self.alpha[index])
+    }
+
+    /// Rec. 601 luma of an entry, scaled up to stay an integer.
+    fn luminance(&self, index: usize) -> u32 {
+        let [r, g, b] = self.entries[index];
+        299 * r as u32 + 587 * g as u32 + 114 * b as u32
+    }
+}
+
+/// Byte and shift of the index of pixel `x` in a row starting at `row`.
+fn index_position(bit_depth: usize, row: usize, x: usize) -> (usize, usize) {
+    let bit = x * bit_depth;
+    (row + bit / 8, 8 - bit_depth - bit % 8)
+}
+
+/// Calls `f` with the palette index of every pixel and stores whatever it returns.
+fn map_indices(
+    image: &mut Image,
+    mut f: impl FnMut(usize) -> crate::Result<usize>,
+) -> crate::Result<()> {
+    let ihdr = *image.ihdr();
+    if ihdr.color_type != ColorType::Indexed {
+        return Err("the palette method needs an indexed-color image".into());
+    }
+
+    let bit_depth = ihdr.bit_depth as usize;
+    let mask = (1 << bit_depth) - 1;
+    let (width, height, stride) = (
+        image.width() as usize,
+        image.height() as usize,
+        image.stride(),
+    );
+    let pixels = image.pixels_mut();
+
+    for y in 0..height {
+        for x in 0..width {
+            let (byte, shift) = index_position(bit_depth, y * stride, x);
+            let index = f((pixels[byte] as usize >> shift) & mask)?;
+            pixels[byte] = (pixels[byte] & !((mask << shift) as u8)) | ((index << shift) as u8);
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces every palette index `i` in `image` with `map[i]`.
+fn remap(image: &mut Image, map: &[usize]) -> crate::Result<()> {
+    map_indices(image, |index| {
+        map.get(index)
+            .copied()
+            .ok_or_else(|| "pixel refers to an entry past the end of the palette".into())
+    })
+}
+
+/// Which palette entries at least one pixel of `image` or the background refers to.
+fn used_entries(image: &Image, palette: &Palette) -> crate::Result<Vec<bool>> {
+    let mut used = vec![false; palette.len()];
+    if let Some(background) = palette.background {
+        used[usize::from(background)] = true;
+    }
+    // Nothing is changed, but reading indices shares the unpacking with `remap`.
+    let mut image = image.clone();
+    map_indices(&mut image, |index| {
+        *used
+            .get_mut(index)
+            .ok_or("pixel refers to an entry past the end of the palette")? = true;
+        Ok(index)
+    })?;
+
+    Ok(used)
+}
+
+/// Sorts the palette by luminance so that entries with neighboring indices look alike, which
+/// keeps LSB changes to the indices from standing out.
+pub fn sort_by_luminance(image: &mut Image, palette: &mut Palette) -> crate::Result<()> {
+    let mut order: Vec<usize> = (0..palette.len()).collect();
+    order.sort_by_key(|&i| (palette.luminance(i), palette.alpha[i], palette.entries[i]));
+
+    palette.permute(image, &order)
+}
+
+/// Arbitrary-precision unsigned integer, stored as little-endian 32-bit limbs, that is just big
+/// enough to convert between bit strings and palette permutations.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct BigUint(Vec<u32>);
+
+impl BigUint {
+    /// Sets `self` to `self * factor + add`.
+    fn mul_add(&mut self, factor: u32, add: u32) {
+        let mut carry = add as u64;
+        for limb in &mut self.0 {
+            let value = *limb as u64 * factor as u64 + carry;
+            *limb = value as u32;
+            carry = value >> 32;
+        }
+        if carry > 0 {
+            self.0.push(carry as u32);
+        }
+    }
+
+    /// Divides `self` by `divisor` in place and returns the remainder.
+    fn div_rem(&mut self, divisor: u32) -> u32 {
+        let mut rem = 0u64;
+        for limb in self.0.iter_mut().rev() {
+            let value = (rem << 32) | *limb as u64;
+            *limb = (value / divisor as u64) as u32;
+            rem = value % divisor as u64;
+        }
+        rem as u32
+    }
+
+    fn bit(&self, i: usize) -> u8 {
+        self.0
+            .get(i / 32)
+            .map_or(0, |limb| (limb >> (i % 32)) as u8 & 1)
+    }
+
+    fn bit_len(&self) -> usize {
+        self.0
+            .iter()
+            .rposition(|&limb| limb != 0)
+            .map_or(0, |i| i * 32 + 32 - self.0[i].leading_zeros() as usize)
+    }
+}
+
+/// Number of payload bits a palette of `len` entries can carry when `used` of them are in use.
+fn bit_capacity(len: usize, used: usize) -> usize {
+    order_bits(used) + (len - used) * 24
+}
+
+/// Number of bits that can be hidden in the order of `n` distinct entries, `floor(log2(n!))`.
+fn order_bits(n: usize) -> usize {
+    let mut factorial = BigUint(vec![1]);
+    for i in 2..=n {
+        factorial.mul_add(i as u32, 0);
+    }
+    factorial.bit_len() - 1
+}
+
+/// Merges identical entries that are in use and moves the used entries to the front of the
+/// palette in canonical order, returning how many there are.
+///
+/// Every other entry is left unused at the end, where its color can be changed freely.
+fn canonicalize(image: &mut Image, palette: &mut Palette) -> crate::Result<usize> {
+    let used = used_entries(image, palette)?;
+
+    let mut merge: Vec<usize> = (0..palette.len()).collect();
+    for i in 0..palette.len() {
+        if used[i] {
+            merge[i] = (0..i)
+                .find(|&j| used[j] && palette.key(j) == palette.key(i))
+                .unwrap_or(i);
+        }
+    }
+    remap(image, &merge)?;
+    if let Some(background) = &mut palette.background {
+        *background = u8::try_from(merge[usize::from(*background)])?;
+    }
+
+    let mut order: Vec<usize> = (0..palette.len())
+        .filter(|&i| used[i] && merge[i] == i)
+        .collect();
+    order.sort_by_key(|&i| palette.key(i));
+    let count = order.len();
+
+    order.extend((0..palette.len()).filter(|&i| !(used[i] && merge[i] == i)));
+    palette.permute(image, &order)?;
+
+    Ok(count)
+}
+
+/// Number of payload bytes `embed_order` can hide in `image` with this palette.
+pub fn order_capacity(image: &Image, palette: &Palette) -> crate::Result<usize> {
+    let mut image = image.clone();
+    let mut palette = palette.clone();
+    let used = canonicalize(&mut image, &mut palette)?;
+
+    Ok((bit_capacity(palette.len(), used) / 8).saturating_sub(LENGTH_PREFIX))
+}
+
+/// Hides `payload`, prefixed with its length, in the order of the used palette entries and in the
+/// colors of the unused ones.
+///
+/// The image looks exactly the same afterwards, only the palette and the indices referring to it
+/// are rearranged.
+pub fn embed_order(image: &mut Image, palette: &mut Palette, payload: &[u8]) -> crate::Result<()> {
+    let used = canonicalize(image, palette)?;
+    let available = bit_capacity(palette.len(), used) / 8;
+
+    if payload.len() + LENGTH_PREFIX > available {
+        return Err(format!(
+            "payload of {} bytes does not fit, the palette can hold {} bytes",
+            payload.len(),
+            available.saturating_sub(LENGTH_PREFIX)
+        )
+        .into());
+    }
+
+    let length = u32::try_from(payload.len())?.to_be_bytes();
+    let mut bits = lsb::bits_of(&length).chain(lsb::bits_of(payload));
+
+    // The first bits pick one of the permutations of the used entries, by their Lehmer code.
+    let mut number = BigUint::default();
+    for _ in 0..order_bits(used) {
+        number.mul_add(2, bits.next().unwrap_or(0) as u32);
+    }
+
+    let mut pool: Vec<usize> = (0..used).collect();
+    let mut order: Vec<usize> = (1..=used)
+        .rev()
+        .map(|radix| pool.remove(number.div_rem(radix as u32) as usize))
+        .collect();
+    order.extend(used..palette.len());
+    palette.permute(image, &order)?;
+
+    // The rest goes into the color bytes of the unused entries.
+    for byte in palette.entries[used..].iter_mut().flatten() {
+        let mut value = *byte;
+        for i in (0..8).rev() {
+            match bits.next() {
+                Some(bit) => value = (value & !(1 << i)) | (bit << i),
+                None => break,
+            }
+        }
+        *byte = value;
+    }
+
+    Ok(())
+}
+
+/// Reads back a payload hidden by `embed_order`.
+pub fn extract_order(image: &Image, palette: &Palette) -> crate::Result<Vec<u8>> {
+    const NOT_FOUND: &str = "no palette payload found";
+
+    let used_flags = used_entries(image, palette)?;
+    let used = used_flags.iter().filter(|&&u| u).count();
+    if used_flags[..used].contains(&false) {
+        return Err(NOT_FOUND.into());
+    }
+
+    // Rank of each used entry in canonical order, which must be unambiguous.
+    let mut canonical: Vec<usize> = (0..used).collect();
+    canonical.sort_by_key(|&i| palette.key(i));
+    if canonical
+        .windows(2)
+        .any(|pair| palette.key(pair[0]) == palette.key(pair[1]))
+    {
+        return Err(NOT_FOUND.into());
+    }
+
+    let mut rank = vec![0; used];
+    for (r, &i) in canonical.iter().enumerate() {
+        rank[i] = r;
+    }
+
+    let mut pool: Vec<usize> = (0..used).collect();
+    let digits: Vec<(usize, usize)> = (0..used)
+        .map(|i| {
+            let digit = pool.iter().position(|&r| r == rank[i]).unwrap_or(0);
+            pool.remove(digit);
+            (digit, used - i)
+        })
+        .collect();
+
+    let mut number = BigUint::default();
+    for &(digit, radix) in digits.iter().rev() {
+        number.mul_add(radix as u32, digit as u32);
+    }
+
+    let order_bits = order_bits(used);
+    let mut bits = (0..order_bits)
+        .rev()
+        .map(|i| number.bit(i))
+        .chain(lsb::bits_of(palette.entries[used..].as_flattened()));
+
+    lsb::read_payload(
+        &mut bits,
+        bit_capacity(palette.len(), used) / 8,
+        NOT_FOUND,
+        NOT_FOUND,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        image::{EncodeOptions, Ihdr},
+        png::Limits,
+    };
+
+    /// A 16x16 indexed image that uses five sixths of its palette, starting with two identical
+    /// entries.
+    fn testing_png(bit_depth: u8) -> Png {
+        let entries: usize = 1 << bit_depth.min(6);
+        let used = entries * 5 / 6;
+        let ihdr = Ihdr {
+            width: 16,
+            height: 16,
+            bit_depth,
+            color_type: ColorType::Indexed,
+            interlace_method: 0,
+        };
+
+        let mut plte: Vec<u8> = (0..entries * 3).map(|i| (i * 97 % 251) as u8).collect();
+        if entries > 4 {
+            plte.copy_within(0..3, 3);
+        }
+        let trns: Vec<u8> = (0..entries / 2)
+            .map(|i| (255 - i.saturating_sub(1) * 7) as u8)
+            .collect();
+
+        let mut image = Image::new(ihdr, vec![0; ihdr.stride().unwrap() * 16]).unwrap();
+        let mut pixel = 0;
+        map_indices(&mut image, |_| {
+            pixel += 1;
+            Ok(pixel * 7 % used)
+        })
+        .unwrap();
+
+        let mut chunks = vec![
+            ihdr.as_chunk(),
+            Chunk::new(ChunkType::from_bytes(*b"PLTE"), plte),
+            Chunk::new(ChunkType::from_bytes(*b"tRNS"), trns),
+            Chunk::new(ChunkType::from_bytes(*b"bKGD"), vec![2]),
+        ];
+        chunks.extend(image.encode(&EncodeOptions::default()).unwrap());
+        chunks.push(Chunk::new(ChunkType::from_bytes(*b"IEND"), vec![]));
+        Png::from_chunks(chunks)
+    }
+
+    /// Color and alpha of every pixel, which must not change when the palette is rearranged.
+    fn rendered(png: &Png) -> Vec<([u8; 3], u8)> {
+        let mut image = Image::decode(png, &Limits::default()).unwrap();
+        let palette = Palette::from_png(png).unwrap();
+        let mut colors = Vec::new();
+        map_indices(&mut image, |index| {
+            colors.push(palette.key(index));
+            Ok(index)
+        })
+        .unwrap();
+        colors
+    }
+
+    fn round_trip(png: &mut Png, f: impl FnOnce(&mut Image, &mut Palette)) {
+        let mut image = Image::decode(png, &Limits::default()).unwrap();
+        let mut palette = Palette::from_png(png).unwrap();
+        f(&mut image, &mut palette);
+        palette.write_into(png).unwrap();
+        image.encode_into(png, &EncodeOptions::default()).unwrap();
+    }
+
+    #[test]
+    fn test_palette_from_png() {
+        let png = testing_png(8);
+        let palette = Palette::from_png(&png).unwrap();
+
+        assert_eq!(palette.len(), 64);
+        assert_eq!(palette.alpha()[2], 248);
+        assert_eq!(palette.alpha()[40], 255);
+    }
+
+    #[test]
+    fn test_sort_keeps_appearance() {
+        let mut png = testing_png(8);
+        let before = rendered(&png);
+
+        round_trip(&mut png, |image, palette| {
+            sort_by_luminance(image, palette).unwrap()
+        });
+
+        assert_eq!(rendered(&png), before);
+
+        let palette = Palette::from_png(&png).unwrap();
+        let luminance: Vec<u32> = (0..palette.len()).map(|i| palette.luminance(i)).collect();
+        assert!(luminance.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        // The tRNS chunk follows its entries to their new positions.
+        let trns = png.chunk_by_type("tRNS").unwrap().data();
+        assert_eq!(trns, &palette.alpha()[..trns.len()]);
+
+        // And so does the background index.
+        let before = Palette::from_png(&testing_png(8)).unwrap();
+        let background = png.chunk_by_type("bKGD").unwrap().data()[0] as usize;
+        assert_eq!(palette.key(background), before.key(2));
+    }
+
+    #[test]
+    fn test_sorted_index_lsb() {
+        let mut png = testing_png(8);
+        let options = lsb::LsbOptions::default();
+
+        round_trip(&mut png, |image, palette| {
+            sort_by_luminance(image, palette).unwrap();
+            lsb::embed_indices(image, b"indexed", &options, palette.len()).unwrap();
+        });
+
+        let image = Image::decode(&png, &Limits::default()).unwrap();
+        let palette = Palette::from_png(&png).unwrap();
+        assert_eq!(
+            lsb::extract_indices(&image, &options, palette.len()).unwrap(),
+            b"indexed"
+        );
+    }
+
+    #[test]
+    fn test_index_lsb_stays_in_palette() {
+        // Five entries, so index 4 has no neighbor to flip to and 4..8 can't take two bits.
+        let ihdr = Ihdr {
+            width: 64,
+            height: 64,
+            bit_depth: 8,
+            color_type: ColorType::Indexed,
+            interlace_method: 0,
+        };
+        let mut image = Image::new(ihdr, vec![0; 64 * 64]).unwrap();
+        let mut pixel = 0;
+        map_indices(&mut image, |_| {
+            pixel += 1;
+            Ok(pixel % 5)
+        })
+        .unwrap();
+
+        // With 8-bit indices every byte is one pixel.
+        let eligible = image.pixels().iter().filter(|&&index| index < 4).count();
+
+        for bits in [1, 2] {
+            let options = lsb::LsbOptions {
+                bits,
+                ..lsb::LsbOptions::default()
+            };
+            let capacity = lsb::index_capacity(&image, &options, 5).unwrap();
+            assert_eq!(capacity, eligible * bits as usize / 8 - LENGTH_PREFIX);
+
+            let mut embedded = image.clone();
+            let payload = vec![0xFF; capacity];
+            lsb::embed_indices(&mut embedded, &payload, &options, 5).unwrap();
+            assert!(embedded.pixels().iter().all(|&index| index < 5));
+            assert_eq!(
+                lsb::extract_indices(&embedded, &options, 5).unwrap(),
+                payload
+            );
+        }
+    }
+
+    #[test]
+    fn test_order_embed_extract() {
+        for bit_depth in [4, 8] {
+            let mut png = testing_png(bit_depth);
+            let before = rendered(&png);
+
+            let image = Image::decode(&png, &Limits::default()).unwrap();
+            let palette = Palette::from_png(&png).unwrap();
+            let payload = vec![0x5A; order_capacity(&image, &palette).unwrap()];
+
+            round_trip(&mut png, |image, palette| {
+                embed_order(image, palette, &payload).unwrap()
+            });
+
+            assert_eq!(rendered(&png), before);
+
+            let image = Image::decode(&png, &Limits::default()).unwrap();
+            let palette = Palette::from_png(&png).unwrap();
+            assert_eq!(extract_order(&image, &palette).unwrap(), payload);
+
+            let original = Palette::from_png(&testing_png(bit_depth)).unwrap();
+            let background = palette.background.unwrap() as usize;
+            assert_eq!(palette.key(background), original.key(2));
+        }
+    }
+
+    #[test]
+    fn test_order_capacity() {
+        let png = testing_png(8);
+        let image = Image::decode(&png, &Limits::default()).unwrap();
+        let palette = Palette::from_png(&png).unwrap();
+
+        // 53 used entries of which two are identical, leaving 52 to order and 12 unused.
+        let bits = order_bits(52) + 12 * 24;
+        assert_eq!(
+            order_capacity(&image, &palette).unwrap(),
+            bits / 8 - LENGTH_PREFIX
+        );
+    }
+
+    #[test]
+    fn test_order_payload_too_large() {
+        let png = testing_png(2);
+        let mut image = Image::decode(&png, &Limits::default()).unwrap();
+        let mut palette = Palette::from_png(&png).unwrap();
+
+        // Three used entries and one unused one can't even hold the length prefix.
+        assert_eq!(order_capacity(&image, &palette).unwrap(), 0);
+        assert!(embed_order(&mut image, &mut palette, &[]).is_err());
+    }
+
+    #[test]
+    fn test_order_bits() {
+        assert_eq!(order_bits(1), 0);
+        assert_eq!(order_bits(4), 4);
+        assert_eq!(order_bits(10), 21);
+        assert_eq!(order_bits(256), 1683);
+    }
+
+    #[test]
+    fn test_big_uint() {
+        let mut number = BigUint::default();
+        for _ in 0..3 {
+            number.mul_add(u32::MAX, 12345);
+        }
+
+        let mut copy = number.clone();
+        assert_eq!(copy.div_rem(u32::MAX), 12345);
+        assert_eq!(copy.div_rem(u32::MAX), 12345);
+        assert_eq!(number.bit(0), 1);
+    }
+
+    #[test]
+    fn test_not_indexed() {
+        let png = Png::try_from(crate::png::tests::PNG_FILE.as_slice()).unwrap();
+        let mut image = Image::decode(&png, &Limits::default()).unwrap();
+
+        assert!(remap(&mut image, &[0]).is_err());
+        assert!(Palette::from_png(&png).is_err());
+    }
+}
@@ -1,55 +1,138 @@
-pub use crate::{chunk::Chunk, chunk_type::ChunkType};
+use crate::format::OrderCheck;
+pub use crate::{
+    chunk::{Chunk, ChunkRef},
+    chunk_type::ChunkType,
+    format::Format,
+    limits::Limits,
+};
 
 use std::{
     convert::TryFrom,
     fmt::{self, Display, Formatter},
-    io::{BufReader, Read},
 };
 
 #[derive(Debug)]
 pub struct Png {
+    format: Format,
     chunks: Vec<Chunk>,
+    /// Bytes after the IEND chunk, or MEND for an MNG, which are written back out unchanged.
+    trailing: Vec<u8>,
 }
 
 impl Png {
     pub const STANDARD_HEADER: &[u8; 8] = &[137, 80, 78, 71, 13, 10, 26, 10];
 
-    fn from_chunks(chunks: Vec<Chunk>) -> Self {
-        Self { chunks }
+    pub fn from_chunks(chunks: Vec<Chunk>) -> Self {
+        Self {
+            format: Format::Png,
+            chunks,
+            trailing: Vec::new(),
+        }
     }
 
     pub fn append_chunk(&mut self, chunk: Chunk) {
         self.chunks.push(chunk);
     }
 
+    /// Inserts `chunk` right before the first chunk of the given type, such as IEND.
+    pub fn insert_chunk_before(&mut self, chunk_type: &str, chunk: Chunk) -> crate::Result<()> {
+        let position = self
+            .chunks
+            .iter()
+            .position(|c| c.chunk_type().bytes() == chunk_type.as_bytes())
+            .ok_or("could not find a chunk with a matching chunk type")?;
+
+        self.chunks.insert(position, chunk);
+
+        Ok(())
+    }
+
+    /// Keeps only the chunks for which `f` returns true.
+    pub fn retain_chunks(&mut self, f: impl FnMut(&Chunk) -> bool) {
+        self.chunks.retain(f);
+    }
+
     pub fn remove_chunk(&mut self, chunk_type: &str) -> crate::Result<Chunk> {
-        Ok(self.chunks.remove(
-            self.chunks
-                .iter()
-                .position(|c| c.chunk_type().bytes() == chunk_type.as_bytes())
-                .unwrap(),
-        ))
+        let position = self
+            .chunks
+            .iter()
+            .position(|c| c.chunk_type().bytes() == chunk_type.as_bytes())
+            .ok_or("could not find a chunk with a matching chunk type")?;
+
+        Ok(self.chunks.remove(position))
+    }
+
+    /// Replaces every chunk of the given type with `chunks`, which are inserted where the first
+    /// of the old chunks was so that the surrounding chunks keep their order.
+    pub fn replace_chunks(&mut self, chunk_type: &str, chunks: Vec<Chunk>) -> crate::Result<()> {
+        let position = self
+            .chunks
+            .iter()
+            .position(|c| c.chunk_type().bytes() == chunk_type.as_bytes())
+            .ok_or("could not find a chunk with a matching chunk type")?;
+
+        self.chunks
+            .retain(|c| c.chunk_type().bytes() != chunk_type.as_bytes());
+        self.chunks.splice(position..position, chunks);
+
+        Ok(())
     }
 
     fn header(&self) -> &[u8; 8] {
-        Self::STANDARD_HEADER
+        self.format.signature()
+    }
+
+    /// Whether this is a PNG, MNG or JNG file, which decides the signature it is written with.
+    pub fn format(&self) -> Format {
+        self.format
     }
 
     pub fn chunks(&self) -> &[Chunk] {
         self.chunks.as_slice()
     }
 
-    fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
+    pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
         self.chunks
             .iter()
             .find(|c| c.chunk_type().bytes() == chunk_type.as_bytes())
     }
 
+    /// Iterates over every chunk of the given type, in file order.
+    pub fn chunks_by_type<'a>(&'a self, chunk_type: &'a str) -> impl Iterator<Item = &'a Chunk> {
+        self.chunks
+            .iter()
+            .filter(move |c| c.chunk_type().bytes() == chunk_type.as_bytes())
+    }
+
+    /// The zlib stream split across the IDAT chunks, joined back together.
+    pub fn idat_data(&self) -> Vec<u8> {
+        self.chunks_by_type("IDAT")
+            .flat_map(|c| c.data())
+            .copied()
+            .collect()
+    }
+
+    /// Bytes that follow the IEND chunk.
+    pub fn trailing(&self) -> &[u8] {
+        &self.trailing
+    }
+
+    /// Replaces whatever follows the IEND chunk with `trailing`.
+    pub fn set_trailing(&mut self, trailing: Vec<u8>) {
+        self.trailing = trailing;
+    }
+
+    /// Parses a PNG, failing with a `LimitError` if the file exceeds any of `limits`.
+    pub fn parse_with_limits(value: &[u8], limits: &Limits) -> crate::Result<Self> {
+        PngRef::parse_with_limits(value, limits).map(Png::from)
+    }
+
     pub fn as_bytes(&self) -> Vec<u8> {
         self.header()
             .iter()
             .copied()
             .chain(self.chunks.iter().flat_map(Chunk::as_bytes))
+            .chain(self.trailing.iter().copied())
             .collect()
     }
 }
@@ -58,18 +141,17 @@ impl TryFrom<&[u8]> for Png {
     type Error = crate::Error;
 
     fn try_from(value: &[u8]) -> crate::Result<Self> {
-        let mut reader = BufReader::new(value);
-
-        let mut header_buf: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 0];
-        reader.read_exact(&mut header_buf)?;
+        Self::parse_with_limits(value, &Limits::default())
+    }
+}
 
-        if &header_buf != Png::STANDARD_HEADER {
-            return Err("invalid header".into());
+impl From<PngRef<'_>> for Png {
+    fn from(png: PngRef<'_>) -> Self {
+        Self {
+            format: png.format(),
+            chunks: png.chunks().map(Chunk::from).collect(),
+            trailing: png.trailing().to_vec(),
         }
-
-        let mut chunks = Vec::<Chunk>::new();
-
-        Ok(Png::from_chunks(chunks))
     }
 }
 
@@ -83,20 +165,138 @@ impl Display for Png {
     }
 }
 
+/// A PNG whose chunks are borrowed from the buffer it was parsed from.
+///
+/// Every chunk is validated when the `PngRef` is created, after which iterating over the chunks
+/// only re-reads their headers and never allocates. Parsing stops at the IEND chunk, or MEND for
+/// an MNG, and anything after it is kept aside as trailing data.
+#[derive(Debug, Clone, Copy)]
+pub struct PngRef<'a> {
+    format: Format,
+    body: &'a [u8],
+    trailing: &'a [u8],
+}
+
+impl<'a> PngRef<'a> {
+    /// Parses a PNG, MNG or JNG file, failing with a `LimitError` if the file exceeds any of
+    /// `limits`.
+    ///
+    /// Length fields are checked before the chunk they describe is read, so a hostile length
+    /// can't make parsing read or allocate past the limits.
+    pub fn parse_with_limits(value: &'a [u8], limits: &Limits) -> crate::Result<Self> {
+        limits.check_file_size(value.len() as u64)?;
+
+        let format = Format::detect(value).ok_or("invalid header")?;
+        let body = &value[format.signature().len()..];
+
+        let mut remaining = body;
+        let mut count = 0;
+        let mut order = OrderCheck::new(format);
+        while !remaining.is_empty() {
+            if let Some(length) = remaining.get(..4) {
+                limits.check_chunk_length(u32::from_be_bytes(length.try_into()?))?;
+            }
+
+            count += 1;
+            limits.check_chunks(count)?;
+
+            let chunk = ChunkRef::from_prefix(remaining)?;
+            remaining = &remaining[chunk.total_len()..];
+
+            if order.next(chunk.chunk_type())? {
+                break;
+            }
+        }
+        order.finish()?;
+
+        let (body, trailing) = body.split_at(body.len() - remaining.len());
+        Ok(Self {
+            format,
+            body,
+            trailing,
+        })
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Iterates over the chunks in file order.
+    pub fn chunks(&self) -> impl Iterator<Item = ChunkRef<'a>> {
+        self.chunk_offsets().map(|(_, chunk)| chunk)
+    }
+
+    /// Iterates over the chunks along with the offset of each one from the start of the file.
+    pub fn chunk_offsets(&self) -> ChunkOffsets<'a> {
+        ChunkOffsets {
+            remaining: self.body,
+            offset: self.format.signature().len(),
+        }
+    }
+
+    pub fn chunk_by_type(&self, chunk_type: &str) -> Option<ChunkRef<'a>> {
+        self.chunks()
+            .find(|c| c.chunk_type().bytes() == chunk_type.as_bytes())
+    }
+
+    /// Bytes that follow the IEND chunk, which decoders ignore.
+    pub fn trailing(&self) -> &'a [u8] {
+        self.trailing
+    }
+
+    /// Offset of the trailing bytes from the start of the file.
+    pub fn trailing_offset(&self) -> usize {
+        self.format.signature().len() + self.body.len()
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for PngRef<'a> {
+    type Error = crate::Error;
+
+    fn try_from(value: &'a [u8]) -> crate::Result<Self> {
+        Self::parse_with_limits(value, &Limits::default())
+    }
+}
+
+/// Iterator over the chunks of a `PngRef` and their offsets, created by `PngRef::chunk_offsets`.
+#[derive(Debug, Clone)]
+pub struct ChunkOffsets<'a> {
+    remaining: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Iterator for ChunkOffsets<'a> {
+    type Item = (usize, ChunkRef<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // The chunks were validated when the `PngRef` was created, so this only fails at the end.
+        let chunk = ChunkRef::parse_prefix(self.remaining).ok()?;
+        let offset = self.offset;
+
+        self.remaining = &self.remaining[chunk.total_len()..];
+        self.offset += chunk.total_len();
+
+        Some((offset, chunk))
+    }
+}
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
-    use crate::{chunk::Chunk, chunk_type::ChunkType};
+    use crate::{
+        chunk::Chunk,
+        chunk_type::ChunkType,
+        limits::{LimitError, LimitKind},
+        text::{TextChunk, TextKind},
+    };
     use std::{convert::TryFrom, str::FromStr};
 
     fn testing_chunks() -> Vec<Chunk> {
-        let mut chunks = Vec::new();
-
-        chunks.push(chunk_from_strings("FrSt", "I am the first chunk").unwrap());
-        chunks.push(chunk_from_strings("miDl", "I am another chunk").unwrap());
-        chunks.push(chunk_from_strings("LASt", "I am the last chunk").unwrap());
-
-        chunks
+        vec![
+            chunk_from_strings("FrSt", "I am the first chunk").unwrap(),
+            chunk_from_strings("miDl", "I am another chunk").unwrap(),
+            chunk_from_strings("LASt", "I am the last chunk").unwrap(),
+        ]
     }
 
     fn testing_png() -> Png {
@@ -201,6 +401,27 @@ mod tests {
         assert_eq!(&chunk.data_as_string().unwrap(), "Message");
     }
 
+    #[test]
+    fn test_insert_chunk_before() {
+        let mut png = testing_png();
+        let chunk = chunk_from_strings("TeSt", "inserted").unwrap();
+        png.insert_chunk_before("LASt", chunk).unwrap();
+
+        assert_eq!(chunk_types(&png), ["FrSt", "miDl", "TeSt", "LASt"]);
+
+        let chunk = chunk_from_strings("TeSt", "inserted").unwrap();
+        assert!(png.insert_chunk_before("IEND", chunk).is_err());
+    }
+
+    #[test]
+    fn test_retain_chunks() {
+        let mut png = testing_png();
+        png.retain_chunks(|c| c.chunk_type().to_string() != "miDl");
+
+        assert_eq!(png.chunks().len(), 2);
+        assert!(png.chunk_by_type("miDl").is_none());
+    }
+
     #[test]
     fn test_remove_chunk() {
         let mut png = testing_png();
@@ -210,6 +431,25 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_replace_chunks() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("miDl", "I am a second middle chunk").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
+
+        png.replace_chunks("miDl", vec![chunk_from_strings("miDl", "New").unwrap()])
+            .unwrap();
+
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["FrSt", "miDl", "LASt", "TeSt"]);
+        assert_eq!(png.chunk_by_type("miDl").unwrap().data(), b"New");
+        assert!(png.replace_chunks("NoNe", vec![]).is_err());
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
@@ -242,8 +482,212 @@ mod tests {
         let _png_string = format!("{}", png);
     }
 
+    #[test]
+    fn test_png_ref_from_image_file() {
+        let png = PngRef::try_from(&PNG_FILE[..]).unwrap();
+        let types: Vec<String> = png.chunks().map(|c| c.chunk_type().to_string()).collect();
+
+        assert_eq!(
+            types,
+            ["IHDR", "sRGB", "gAMA", "pHYs", "IDAT", "RuSt", "IEND"]
+        );
+    }
+
+    #[test]
+    fn test_png_ref_chunk_offsets() {
+        let png = PngRef::try_from(&PNG_FILE[..]).unwrap();
+
+        for (offset, chunk) in png.chunk_offsets() {
+            let at_offset = ChunkRef::from_prefix(&PNG_FILE[offset..]).unwrap();
+            assert_eq!(at_offset.chunk_type(), chunk.chunk_type());
+            assert_eq!(at_offset.data().as_ptr(), chunk.data().as_ptr());
+        }
+    }
+
+    #[test]
+    fn test_png_ref_chunk_by_type() {
+        let png = PngRef::try_from(&PNG_FILE[..]).unwrap();
+        let chunk = png.chunk_by_type("RuSt").unwrap();
+
+        assert_eq!(chunk.data(), b"hey");
+    }
+
+    #[test]
+    fn test_png_ref_into_png() {
+        let png = Png::from(PngRef::try_from(&PNG_FILE[..]).unwrap());
+        assert_eq!(png.as_bytes(), PNG_FILE.to_vec());
+    }
+
+    #[test]
+    fn test_png_ref_truncated() {
+        let png = PngRef::try_from(&PNG_FILE[..PNG_FILE.len() - 1]);
+        assert!(png.is_err());
+    }
+
+    #[test]
+    fn test_png_ref_trailing() {
+        let bytes = [&PNG_FILE[..], b"not a chunk"].concat();
+        let png = PngRef::try_from(&bytes[..]).unwrap();
+
+        assert_eq!(png.trailing(), b"not a chunk");
+        assert_eq!(png.trailing_offset(), PNG_FILE.len());
+        assert_eq!(
+            png.chunks().last().unwrap().chunk_type().to_string(),
+            "IEND"
+        );
+
+        let png = PngRef::try_from(&PNG_FILE[..]).unwrap();
+        assert!(png.trailing().is_empty());
+    }
+
+    #[test]
+    fn test_png_trailing_round_trip() {
+        let bytes = [&PNG_FILE[..], b"after the end"].concat();
+        let mut png = Png::try_from(&bytes[..]).unwrap();
+
+        assert_eq!(png.trailing(), b"after the end");
+        assert_eq!(png.as_bytes(), bytes);
+
+        png.set_trailing(Vec::new());
+        assert_eq!(png.as_bytes(), PNG_FILE.to_vec());
+    }
+
+    /// An MNG holding the test image as its only PNG datastream.
+    fn testing_mng(trailing: &[u8]) -> Vec<u8> {
+        let mhdr = Chunk::new(ChunkType::from_bytes(*b"MHDR"), vec![0; 28]);
+        let mend = Chunk::new(ChunkType::from_bytes(*b"MEND"), vec![]);
+
+        [
+            &Format::Mng.signature()[..],
+            &mhdr.as_bytes(),
+            &PNG_FILE[8..],
+            &mend.as_bytes(),
+            trailing,
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn test_mng() {
+        let bytes = testing_mng(b"after MEND");
+        let png = PngRef::try_from(&bytes[..]).unwrap();
+
+        // The IEND of the embedded PNG doesn't end the MNG.
+        let types: Vec<String> = png.chunks().map(|c| c.chunk_type().to_string()).collect();
+        assert_eq!(png.format(), Format::Mng);
+        assert_eq!(types.first().unwrap(), "MHDR");
+        assert_eq!(&types[types.len() - 2..], ["IEND", "MEND"]);
+        assert_eq!(png.trailing(), b"after MEND");
+        assert_eq!(Png::from(png).as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_mng_order() {
+        let bytes = testing_mng(b"");
+
+        // Missing MEND.
+        let truncated = &bytes[..bytes.len() - 12];
+        assert!(PngRef::try_from(truncated).is_err());
+
+        // A PNG datastream behind an MNG signature.
+        let png_only = [&Format::Mng.signature()[..], &PNG_FILE[8..]].concat();
+        assert!(PngRef::try_from(&png_only[..]).is_err());
+    }
+
+    #[test]
+    fn test_jng() {
+        let chunk = |chunk_type: &[u8; 4], data: Vec<u8>| {
+            Chunk::new(ChunkType::from_bytes(*chunk_type), data).as_bytes()
+        };
+        let jhdr = chunk(b"JHDR", vec![0; 16]);
+        let jdat = chunk(b"JDAT", vec![0xFF, 0xD8, 0xFF, 0xD9]);
+        let iend = chunk(b"IEND", vec![]);
+
+        let bytes = [&Format::Jng.signature()[..], &jhdr, &jdat, &iend].concat();
+        let jng = Png::try_from(&bytes[..]).unwrap();
+        assert_eq!(jng.format(), Format::Jng);
+        assert_eq!(jng.chunks().len(), 3);
+        assert_eq!(jng.as_bytes(), bytes);
+
+        let no_jdat = [&Format::Jng.signature()[..], &jhdr, &iend].concat();
+        assert!(Png::try_from(&no_jdat[..]).is_err());
+
+        let no_jhdr = [&Format::Jng.signature()[..], &jdat, &iend].concat();
+        assert!(Png::try_from(&no_jhdr[..]).is_err());
+    }
+
+    #[test]
+    fn test_remove_missing_chunk() {
+        let mut png = testing_png();
+        assert!(png.remove_chunk("TeSt").is_err());
+    }
+
+    #[test]
+    fn test_hostile_chunk_length() {
+        #[rustfmt::skip]
+        let bytes: Vec<u8> = Png::STANDARD_HEADER
+            .iter()
+            .chain(&[
+                0x7F, 0xFF, 0xFF, 0xFF, // length
+                82, 117, 83, 116,       // Chunk Type
+                1, 2, 3, 4,             // Data
+            ])
+            .copied()
+            .collect();
+
+        let err = Png::try_from(bytes.as_ref()).unwrap_err();
+        let err = err.downcast_ref::<LimitError>().unwrap();
+        assert_eq!(err.kind(), LimitKind::ChunkLength);
+
+        // Without a length limit the chunk still can't read past the end of the input.
+        assert!(Png::parse_with_limits(&bytes, &Limits::UNLIMITED).is_err());
+    }
+
+    #[test]
+    fn test_chunk_count_limit() {
+        let limits = Limits {
+            max_chunks: 6,
+            ..Limits::default()
+        };
+
+        let err = Png::parse_with_limits(&PNG_FILE, &limits).unwrap_err();
+        let err = err.downcast_ref::<LimitError>().unwrap();
+        assert_eq!(err.kind(), LimitKind::Chunks);
+    }
+
+    #[test]
+    fn test_file_size_limit() {
+        let limits = Limits {
+            max_file_size: 1024,
+            ..Limits::default()
+        };
+
+        let err = PngRef::parse_with_limits(&PNG_FILE, &limits).unwrap_err();
+        let err = err.downcast_ref::<LimitError>().unwrap();
+        assert_eq!(err.kind(), LimitKind::FileSize);
+    }
+
+    /// The test PNG, which has sRGB, gAMA and pHYs chunks before its image data, with a tEXt
+    /// chunk added after it.
+    pub(crate) fn png_with_text() -> Png {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let text = TextChunk::new(TextKind::Plain, "Author", "someone")
+            .unwrap()
+            .as_chunk()
+            .unwrap();
+        png.insert_chunk_before("IEND", text).unwrap();
+        png
+    }
+
+    pub(crate) fn chunk_types(png: &Png) -> Vec<String> {
+        png.chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect()
+    }
+
     // This is the raw bytes for a shrunken version of the `dice.png` image on Wikipedia
-    const PNG_FILE: [u8; 4803] = [
+    pub(crate) const PNG_FILE: [u8; 4803] = [
         137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 13, 73, 72, 68, 82, 0, 0, 0, 50, 0, 0, 0, 50, 8,
         6, 0, 0, 0, 30, 63, 136, 177, 0, 0, 0, 1, 115, 82, 71, 66, 0, 174, 206, 28, 233, 0, 0, 0,
         4, 103, 65, 77, 65, 0, 0, 177, 143, 11, 252, 97, 5, 0, 0, 0, 9, 112, 72, 89, 115, 0, 0, 14,
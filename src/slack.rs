@@ -0,0 +1,124 @@
+use crate::{
+    lsb::LENGTH_PREFIX,
+    png::{Chunk, ChunkType, Limits, Png},
+    zlib,
+};
+
+/// Hides `payload` after the end of the zlib stream in the IDAT chunks.
+///
+/// Decoders stop at the end of the zlib stream, so the pixels stay exactly the same and no chunk
+/// is added. The stream keeps the original IDAT sizes, with the payload appended to the chunk the
+/// stream ends in and any earlier payload removed.
+pub fn embed(png: &mut Png, payload: &[u8], limits: &Limits) -> crate::Result<()> {
+    let idat = png.idat_data();
+    let stream = &idat[..zlib::stream_len(&idat, limits)?];
+    let sizes: Vec<usize> = png.chunks_by_type("IDAT").map(|c| c.data().len()).collect();
+
+    let mut chunks = Vec::new();
+    let mut remaining = stream;
+    for size in sizes {
+        if remaining.is_empty() {
+            break;
+        }
+
+        let (data, rest) = remaining.split_at(size.min(remaining.len()));
+        chunks.push(data.to_vec());
+        remaining = rest;
+    }
+
+    let last = chunks.last_mut().ok_or("missing IDAT chunk")?;
+    last.extend(u32::try_from(payload.len())?.to_be_bytes());
+    last.extend(payload);
+
+    let len = u32::try_from(last.len()).map_err(|_| "payload is too large for an IDAT chunk")?;
+    limits.check_chunk_length(len)?;
+
+    png.replace_chunks(
+        "IDAT",
+        chunks
+            .into_iter()
+            .map(|data| Chunk::new(ChunkType::from_bytes(*b"IDAT"), data))
+            .collect(),
+    )
+}
+
+/// Reads back a payload hidden by `embed`.
+pub fn extract(png: &Png, limits: &Limits) -> crate::Result<Vec<u8>> {
+    let idat = png.idat_data();
+    let slack = &idat[zlib::stream_len(&idat, limits)?..];
+
+    match slack.split_at_checked(LENGTH_PREFIX) {
+        Some((length, payload))
+            if u32::from_be_bytes(length.try_into()?) as usize == payload.len() =>
+        {
+            Ok(payload.to_vec())
+        }
+        _ => Err("no payload found after the end of the zlib stream".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        image::{EncodeOptions, Image},
+        png::tests::PNG_FILE,
+    };
+
+    fn testing_png(idat_size: usize) -> Png {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let image = Image::decode(&png, &Limits::default()).unwrap();
+        let options = EncodeOptions {
+            idat_size,
+            ..EncodeOptions::default()
+        };
+
+        image.encode_into(&mut png, &options).unwrap();
+        png
+    }
+
+    fn idat_sizes(png: &Png) -> Vec<usize> {
+        png.chunks_by_type("IDAT").map(|c| c.data().len()).collect()
+    }
+
+    #[test]
+    fn test_embed_extract() {
+        let mut png = testing_png(1000);
+        let original = Image::decode(&png, &Limits::default()).unwrap();
+        let sizes = idat_sizes(&png);
+
+        embed(&mut png, b"after the stream", &Limits::default()).unwrap();
+
+        assert_eq!(
+            extract(&png, &Limits::default()).unwrap(),
+            b"after the stream"
+        );
+
+        // Only the last IDAT grows, and the pixels are untouched.
+        let mut expected = sizes.clone();
+        *expected.last_mut().unwrap() += LENGTH_PREFIX + 16;
+        assert_eq!(idat_sizes(&png), expected);
+        assert_eq!(
+            Image::decode(&png, &Limits::default()).unwrap().pixels(),
+            original.pixels()
+        );
+    }
+
+    #[test]
+    fn test_embed_replaces_payload() {
+        let mut png = testing_png(1000);
+        let sizes = idat_sizes(&png);
+
+        embed(&mut png, &[1; 5000], &Limits::default()).unwrap();
+        embed(&mut png, b"second", &Limits::default()).unwrap();
+
+        assert_eq!(extract(&png, &Limits::default()).unwrap(), b"second");
+        assert_eq!(idat_sizes(&png).len(), sizes.len());
+    }
+
+    #[test]
+    fn test_no_payload() {
+        let png = testing_png(1000);
+        assert!(extract(&png, &Limits::default()).is_err());
+    }
+}
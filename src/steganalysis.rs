@@ -0,0 +1,471 @@
+use crate::image::{ColorType, Image};
+use std::fmt::{self, Display, Formatter};
+
+/// One color channel of an image as a grid of 8-bit values.
+///
+/// 16-bit samples are represented by their low byte, which is where LSB embedding puts its bits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plane {
+    width: usize,
+    values: Vec<u8>,
+}
+
+impl Plane {
+    fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        self.values.chunks_exact(self.width)
+    }
+}
+
+/// Splits the color channels of `image` into planes, leaving out alpha.
+pub fn planes(image: &Image) -> crate::Result<Vec<Plane>> {
+    let ihdr = image.ihdr();
+    let colors = match ihdr.color_type {
+        ColorType::Grayscale | ColorType::GrayscaleAlpha => 1,
+        ColorType::Rgb | ColorType::Rgba => 3,
+        ColorType::Indexed => {
+            return Err("statistical tests don't apply to palette indices".into());
+        }
+    };
+    if ihdr.bit_depth < 8 {
+        return Err("statistical tests need at least 8 bits per sample".into());
+    }
+
+    let bytes_per_sample = ihdr.bit_depth as usize / 8;
+    let channels = ihdr.color_type.channels();
+    let width = image.width() as usize;
+
+    Ok((0..colors)
+        .map(|channel| {
+            let values = (0..image.height())
+                .flat_map(|y| {
+                    let row = image.row(y);
+                    (0..width)
+                        .map(move |x| row[(x * channels + channel + 1) * bytes_per_sample - 1])
+                })
+                .collect();
+
+            Plane { width, values }
+        })
+        .collect())
+}
+
+/// The statistical tests for LSB embedding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Test {
+    ChiSquare,
+    Rs,
+    SamplePair,
+}
+
+impl Display for Test {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Test::ChiSquare => "chi-square",
+            Test::Rs => "RS analysis",
+            Test::SamplePair => "sample pair analysis",
+        })
+    }
+}
+
+/// The outcome of one statistical test.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Detection {
+    pub test: Test,
+    /// How confident the test is that the image carries an LSB payload, from 0 to 1.
+    ///
+    /// For the chi-square test this is the probability of the observed histogram under
+    /// embedding. RS and sample pair analysis estimate the fraction of samples that carry payload
+    /// bits, which serves as the score.
+    pub confidence: f64,
+}
+
+impl Display for Detection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {:.1}%", self.test, self.confidence * 100.0)
+    }
+}
+
+/// Runs every statistical test on the color channels of `image`.
+pub fn detect(image: &Image) -> crate::Result<Vec<Detection>> {
+    let planes = planes(image)?;
+
+    Ok(vec![
+        Detection {
+            test: Test::ChiSquare,
+            confidence: chi_square(&planes),
+        },
+        Detection {
+            test: Test::Rs,
+            confidence: rs_analysis(&planes),
+        },
+        Detection {
+            test: Test::SamplePair,
+            confidence: sample_pair_analysis(&planes),
+        },
+    ])
+}
+
+/// Natural logarithm of the gamma function, using the Lanczos approximation.
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.180_091_729_471_46,
+        -86.505_320_329_416_77,
+        24.014_098_240_830_91,
+        -1.231_739_572_450_155,
+        0.001_208_650_973_866_179,
+        -0.000_005_395_239_384_953,
+    ];
+
+    let tmp = x + 5.5;
+    let series = COEFFICIENTS
+        .iter()
+        .enumerate()
+        .fold(1.000_000_000_190_015, |sum, (i, c)| {
+            sum + c / (x + 1.0 + i as f64)
+        });
+
+    -tmp + (x + 0.5) * tmp.ln() + (2.506_628_274_631_000_5 * series / x).ln()
+}
+
+/// Regularized lower incomplete gamma function `P(a, x)`.
+fn gamma_p(a: f64, x: f64) -> f64 {
+    const EPSILON: f64 = 1e-12;
+    const MAX_ITERATIONS: usize = 1000;
+
+    if x <= 0.0 {
+        return 0.0;
+    }
+
+    let prefix = (-x + a * x.ln() - ln_gamma(a)).exp();
+
+    if x < a + 1.0 {
+        // The series converges quickly below the mean.
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        for n in 1..MAX_ITERATIONS {
+            term *= x / (a + n as f64);
+            sum += term;
+            if term.abs() < sum.abs() * EPSILON {
+                break;
+            }
+        }
+        (sum * prefix).min(1.0)
+    } else {
+        // Lentz's method for the continued fraction of the upper function.
+        let tiny = f64::MIN_POSITIVE / EPSILON;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..MAX_ITERATIONS {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < EPSILON {
+                break;
+            }
+        }
+        (1.0 - prefix * h).max(0.0)
+    }
+}
+
+/// Number of growing prefixes of the image the chi-square test is run on.
+const CHI_SQUARE_STEPS: usize = 10;
+
+/// Probability that the value pairs `2k` and `2k + 1` in `histogram` were evened out.
+fn chi_square_probability(histogram: &[u64; 256]) -> f64 {
+    let mut statistic = 0.0;
+    let mut categories = 0;
+    for pair in histogram.chunks_exact(2) {
+        let expected = (pair[0] + pair[1]) as f64 / 2.0;
+        // Sparse pairs say little and would dominate the statistic.
+        if expected < 5.0 {
+            continue;
+        }
+
+        statistic += (pair[0] as f64 - expected).powi(2) / expected;
+        categories += 1;
+    }
+
+    if categories < 2 {
+        return 0.0;
+    }
+
+    1.0 - gamma_p((categories - 1) as f64 / 2.0, statistic / 2.0)
+}
+
+/// Westfeld and Pfitzmann's chi-square attack.
+///
+/// Replacing LSBs with message bits evens out the counts of each pair of values `2k` and `2k + 1`.
+/// Sequential embedding only does so at the start of the image, so the test is run on growing
+/// prefixes of it and the highest probability wins.
+fn chi_square(planes: &[Plane]) -> f64 {
+    let len = planes.first().map_or(0, |plane| plane.values.len());
+    let mut histogram = [0u64; 256];
+    let mut probability: f64 = 0.0;
+    let mut start = 0;
+
+    for step in 1..=CHI_SQUARE_STEPS {
+        let end = len * step / CHI_SQUARE_STEPS;
+        for i in start..end {
+            for plane in planes {
+                histogram[plane.values[i] as usize] += 1;
+            }
+        }
+        start = end;
+
+        probability = probability.max(chi_square_probability(&histogram));
+    }
+
+    probability
+}
+
+/// Counts of regular and singular groups for one flipping function, as fractions of all groups.
+#[derive(Debug, Clone, Copy, Default)]
+struct RsCounts {
+    regular: f64,
+    singular: f64,
+}
+
+impl RsCounts {
+    fn difference(&self) -> f64 {
+        self.regular - self.singular
+    }
+}
+
+/// Smoothness of a group of samples, the sum of differences between neighbors.
+fn smoothness(group: &[i16]) -> i32 {
+    group
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0]).abs() as i32)
+        .sum()
+}
+
+/// Classifies every group of four horizontally adjacent samples by whether flipping the LSBs of the
+/// middle two makes it smoother or rougher, for the positive and the negative flipping function.
+///
+/// `flip_all` first flips the LSB of every sample, which is how RS analysis simulates embedding in
+/// the remaining samples.
+fn rs_counts(planes: &[Plane], flip_all: bool) -> (RsCounts, RsCounts) {
+    const MASK: [bool; 4] = [false, true, true, false];
+
+    let positive = |x: i16| x ^ 1;
+    let negative = |x: i16| ((x + 1) ^ 1) - 1;
+
+    let mut counts = (RsCounts::default(), RsCounts::default());
+    let mut groups = 0;
+
+    for plane in planes {
+        for row in plane.rows() {
+            for chunk in row.chunks_exact(MASK.len()) {
+                let mut group = [0i16; 4];
+                for (g, &value) in group.iter_mut().zip(chunk) {
+                    *g = if flip_all { value ^ 1 } else { value } as i16;
+                }
+
+                let original = smoothness(&group);
+                let classify = |counts: &mut RsCounts, flip: &dyn Fn(i16) -> i16| {
+                    let mut flipped = group;
+                    for (value, _) in flipped.iter_mut().zip(MASK).filter(|(_, m)| *m) {
+                        *value = flip(*value);
+                    }
+
+                    let changed = smoothness(&flipped);
+                    if changed > original {
+                        counts.regular += 1.0;
+                    } else if changed < original {
+                        counts.singular += 1.0;
+                    }
+                };
+
+                classify(&mut counts.0, &positive);
+                classify(&mut counts.1, &negative);
+                groups += 1;
+            }
+        }
+    }
+
+    if groups > 0 {
+        for counts in [&mut counts.0, &mut counts.1] {
+            counts.regular /= groups as f64;
+            counts.singular /= groups as f64;
+        }
+    }
+
+    counts
+}
+
+/// Smaller root of `a x² + b x + c`, or `None` if there is no real root.
+fn smaller_root(a: f64, b: f64, c: f64) -> Option<f64> {
+    if a.abs() < f64::EPSILON {
+        return (b.abs() >= f64::EPSILON).then(|| -c / b);
+    }
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let roots = [
+        (-b + discriminant.sqrt()) / (2.0 * a),
+        (-b - discriminant.sqrt()) / (2.0 * a),
+    ];
+    roots.into_iter().min_by(|x, y| x.abs().total_cmp(&y.abs()))
+}
+
+/// Fridrich, Goljan and Du's RS analysis, estimating the fraction of samples that carry payload.
+fn rs_analysis(planes: &[Plane]) -> f64 {
+    let (m, negative_m) = rs_counts(planes, false);
+    let (m_flipped, negative_m_flipped) = rs_counts(planes, true);
+
+    let d0 = m.difference();
+    let d1 = m_flipped.difference();
+    let n0 = negative_m.difference();
+    let n1 = negative_m_flipped.difference();
+
+    let Some(z) = smaller_root(2.0 * (d1 + d0), n0 - n1 - d1 - 3.0 * d0, d0 - n0) else {
+        return 0.0;
+    };
+
+    let rate = z / (z - 0.5);
+    if rate.is_finite() {
+        rate.clamp(0.0, 1.0)
+    } else {
+        0.0
+    }
+}
+
+/// Dumitrescu, Wu and Wang's sample pair analysis, estimating the fraction of samples that carry
+/// payload from how horizontally adjacent pairs of samples are distributed.
+fn sample_pair_analysis(planes: &[Plane]) -> f64 {
+    let (mut x, mut y, mut k, mut pairs) = (0u64, 0u64, 0u64, 0u64);
+
+    for plane in planes {
+        for row in plane.rows() {
+            for pair in row.windows(2) {
+                let (u, v) = (pair[0], pair[1]);
+
+                if (v % 2 == 0 && u < v) || (v % 2 == 1 && u > v) {
+                    x += 1;
+                }
+                if (v % 2 == 0 && u > v) || (v % 2 == 1 && u < v) {
+                    y += 1;
+                }
+                if u / 2 == v / 2 {
+                    k += 1;
+                }
+                pairs += 1;
+            }
+        }
+    }
+
+    if k == 0 {
+        return 0.0;
+    }
+
+    let a = 2.0 * k as f64;
+    let b = 2.0 * (2.0 * x as f64 - pairs as f64);
+    let c = y as f64 - x as f64;
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return 0.0;
+    }
+
+    // The smaller root estimates half the fraction of samples that carry payload.
+    let beta = ((-b - discriminant.sqrt()) / (2.0 * a)).min((-b + discriminant.sqrt()) / (2.0 * a));
+    (2.0 * beta).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        lsb::{self, LsbOptions},
+        png::{Limits, Png},
+    };
+
+    fn testing_image() -> Image {
+        let png = Png::try_from(crate::png::tests::PNG_FILE.as_slice()).unwrap();
+        Image::decode(&png, &Limits::default()).unwrap()
+    }
+
+    /// Fills a fraction of the LSB capacity of the image with pseudo-random bytes.
+    fn embedded_image(fraction: f64) -> Image {
+        let mut image = testing_image();
+        let options = LsbOptions::default();
+        let len = lsb::capacity(&image, &options).unwrap() as f64 * fraction;
+        let payload: Vec<u8> = (0..len as usize)
+            .map(|i| (i as u32).wrapping_mul(2_654_435_761).rotate_right(13) as u8)
+            .collect();
+
+        lsb::embed(&mut image, &payload, &options).unwrap();
+        image
+    }
+
+    fn confidence(detections: &[Detection], test: Test) -> f64 {
+        detections
+            .iter()
+            .find(|d| d.test == test)
+            .unwrap()
+            .confidence
+    }
+
+    #[test]
+    fn test_planes() {
+        let image = testing_image();
+        let planes = planes(&image).unwrap();
+
+        assert_eq!(planes.len(), 3);
+        assert_eq!(planes[0].values.len(), 2500);
+        assert_eq!(planes[1].values[0], image.pixels()[1]);
+    }
+
+    #[test]
+    fn test_gamma_p() {
+        // P(1, x) = 1 - e^-x
+        for x in [0.1, 1.0, 5.0] {
+            assert!((gamma_p(1.0, x) - (1.0 - (-x).exp())).abs() < 1e-9);
+        }
+        assert!((ln_gamma(5.0) - 24f64.ln()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_detects_full_embedding() {
+        let clean = detect(&testing_image()).unwrap();
+        let embedded = detect(&embedded_image(1.0)).unwrap();
+
+        for test in [Test::ChiSquare, Test::Rs, Test::SamplePair] {
+            assert!(
+                confidence(&embedded, test) > confidence(&clean, test) + 0.3,
+                "{test} did not tell the images apart: {clean:?} {embedded:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_chi_square_detects_partial_embedding() {
+        let clean = planes(&testing_image()).unwrap();
+        let embedded = planes(&embedded_image(0.5)).unwrap();
+
+        assert!(chi_square(&clean) < 0.01);
+        assert!(chi_square(&embedded) > 0.99);
+    }
+
+    #[test]
+    fn test_smaller_root() {
+        assert_eq!(smaller_root(1.0, -3.0, 2.0), Some(1.0));
+        assert_eq!(smaller_root(0.0, 2.0, -1.0), Some(0.5));
+        assert_eq!(smaller_root(1.0, 0.0, 1.0), None);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::png::{Chunk, ChunkType, Png};
+
+/// Which chunks `strip` leaves in place. Standard critical chunks are always kept, since the
+/// image can't be decoded without them.
+#[derive(Debug, Clone, Default)]
+pub struct StripOptions {
+    /// Ancillary chunk types to keep.
+    pub keep: Vec<ChunkType>,
+    /// Chunk types to remove even if they would otherwise be kept. These win over `keep`, and
+    /// are the only way to remove private critical chunks.
+    pub remove: Vec<ChunkType>,
+    /// Keep the ancillary chunks that are safe to copy, which don't depend on the image data.
+    pub safe_to_copy: bool,
+    /// Keep whatever follows the end chunk instead of clearing it.
+    pub keep_trailing: bool,
+}
+
+impl StripOptions {
+    /// Whether chunks of this type stay in the file.
+    pub fn keeps(&self, chunk_type: &ChunkType) -> bool {
+        if self.remove.contains(chunk_type) {
+            return false;
+        }
+        if chunk_type.is_critical() {
+            return true;
+        }
+
+        self.keep.contains(chunk_type) || (self.safe_to_copy && chunk_type.is_safe_to_copy())
+    }
+}
+
+/// What `strip` took out of a file, and what it left that may still hide data.
+#[derive(Debug, Clone, Default)]
+pub struct StripReport {
+    /// Removed chunks, grouped by type in the order the types first appear.
+    pub removed: Vec<Chunk>,
+    /// Number of bytes that followed the end chunk and were cleared.
+    pub trailing: usize,
+    /// Private critical chunks that were kept, which no standard decoder needs.
+    pub kept_private: Vec<ChunkType>,
+}
+
+/// Removes every ancillary chunk that `options` doesn't keep, along with any data after the end
+/// chunk.
+pub fn strip(png: &mut Png, options: &StripOptions) -> crate::Result<StripReport> {
+    if let Some(critical) = options
+        .remove
+        .iter()
+        .find(|t| t.is_critical() && t.is_public())
+    {
+        return Err(format!("{critical} is a critical chunk and can't be removed").into());
+    }
+
+    let mut report = StripReport::default();
+    let mut types: Vec<ChunkType> = Vec::new();
+    for chunk_type in png.chunks().iter().map(Chunk::chunk_type) {
+        if !options.keeps(chunk_type) {
+            if !types.contains(chunk_type) {
+                types.push(*chunk_type);
+            }
+        } else if chunk_type.is_critical()
+            && !chunk_type.is_public()
+            && !report.kept_private.contains(chunk_type)
+        {
+            report.kept_private.push(*chunk_type);
+        }
+    }
+
+    for chunk_type in types {
+        let chunk_type = chunk_type.to_string();
+        while let Ok(chunk) = png.remove_chunk(&chunk_type) {
+            report.removed.push(chunk);
+        }
+    }
+
+    if !options.keep_trailing {
+        report.trailing = png.trailing().len();
+        png.set_trailing(Vec::new());
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::{chunk_types, png_with_text};
+
+    #[test]
+    fn test_strip() {
+        let mut png = png_with_text();
+        png.set_trailing(b"SECRET GPS DATA".to_vec());
+        let report = strip(&mut png, &StripOptions::default()).unwrap();
+
+        // RuSt is a private chunk, but a critical one.
+        assert_eq!(chunk_types(&png), ["IHDR", "IDAT", "RuSt", "IEND"]);
+        let removed: Vec<String> = report
+            .removed
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(removed, ["sRGB", "gAMA", "pHYs", "tEXt"]);
+        assert_eq!(report.kept_private, ["RuSt".parse().unwrap()]);
+
+        assert_eq!(report.trailing, 15);
+        assert!(png.trailing().is_empty());
+    }
+
+    #[test]
+    fn test_keep_trailing() {
+        let mut png = png_with_text();
+        png.set_trailing(b"kept".to_vec());
+        let options = StripOptions {
+            keep_trailing: true,
+            ..StripOptions::default()
+        };
+        let report = strip(&mut png, &options).unwrap();
+
+        assert_eq!(report.trailing, 0);
+        assert_eq!(png.trailing(), b"kept");
+    }
+
+    #[test]
+    fn test_keep_and_remove() {
+        let mut png = png_with_text();
+        let options = StripOptions {
+            keep: vec!["sRGB".parse().unwrap(), "tEXt".parse().unwrap()],
+            remove: vec!["tEXt".parse().unwrap(), "RuSt".parse().unwrap()],
+            safe_to_copy: true,
+            keep_trailing: false,
+        };
+        let report = strip(&mut png, &options).unwrap();
+
+        assert_eq!(chunk_types(&png), ["IHDR", "sRGB", "pHYs", "IDAT", "IEND"]);
+        assert!(report.kept_private.is_empty());
+    }
+
+    #[test]
+    fn test_remove_critical() {
+        let options = StripOptions {
+            remove: vec!["IDAT".parse().unwrap()],
+            ..StripOptions::default()
+        };
+        assert!(strip(&mut png_with_text(), &options).is_err());
+    }
+}
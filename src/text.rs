@@ -0,0 +1,265 @@
+use crate::{
+    png::{Chunk, ChunkType, Limits, PngRef},
+    zlib,
+};
+
+/// The three kinds of PNG text chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextKind {
+    /// tEXt, uncompressed Latin-1 text.
+    Plain,
+    /// zTXt, compressed Latin-1 text.
+    Compressed,
+    /// iTXt, UTF-8 text that may be compressed.
+    International,
+}
+
+impl TextKind {
+    pub fn chunk_type(&self) -> ChunkType {
+        ChunkType::from_bytes(match self {
+            TextKind::Plain => *b"tEXt",
+            TextKind::Compressed => *b"zTXt",
+            TextKind::International => *b"iTXt",
+        })
+    }
+
+    pub fn from_chunk_type(chunk_type: &ChunkType) -> Option<Self> {
+        match &chunk_type.bytes() {
+            b"tEXt" => Some(TextKind::Plain),
+            b"zTXt" => Some(TextKind::Compressed),
+            b"iTXt" => Some(TextKind::International),
+            _ => None,
+        }
+    }
+}
+
+/// A keyword and the text stored under it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextChunk {
+    kind: TextKind,
+    keyword: String,
+    text: String,
+}
+
+fn latin1_decode(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+fn latin1_encode(s: &str) -> crate::Result<Vec<u8>> {
+    s.chars()
+        .map(|c| u8::try_from(c).map_err(|_| format!("'{c}' is not a Latin-1 character").into()))
+        .collect()
+}
+
+/// Splits `bytes` at the first null byte, which separates the fields of a text chunk.
+fn split_null(bytes: &[u8]) -> crate::Result<(&[u8], &[u8])> {
+    let position = bytes
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or("text chunk field is not null-terminated")?;
+
+    Ok((&bytes[..position], &bytes[position + 1..]))
+}
+
+/// Checks that `keyword` is 1 to 79 printable Latin-1 characters without leading, trailing or
+/// consecutive spaces, as the PNG specification requires.
+fn validate_keyword(keyword: &str) -> crate::Result<()> {
+    let bytes = latin1_encode(keyword)?;
+
+    if bytes.is_empty() || bytes.len() > 79 {
+        return Err("keyword must be between 1 and 79 characters long".into());
+    }
+    if !bytes.iter().all(|&b| (32..=126).contains(&b) || b >= 161) {
+        return Err("keyword may only contain printable characters".into());
+    }
+    if keyword.starts_with(' ') || keyword.ends_with(' ') || keyword.contains("  ") {
+        return Err("keyword may not have leading, trailing or consecutive spaces".into());
+    }
+
+    Ok(())
+}
+
+impl TextChunk {
+    pub fn new(kind: TextKind, keyword: &str, text: &str) -> crate::Result<Self> {
+        validate_keyword(keyword)?;
+        if kind != TextKind::International {
+            latin1_encode(text)?;
+        }
+
+        Ok(Self {
+            kind,
+            keyword: keyword.to_string(),
+            text: text.to_string(),
+        })
+    }
+
+    /// Parses the data of a tEXt, zTXt or iTXt chunk, inflating compressed text within `limits`.
+    pub fn parse(chunk_type: &ChunkType, data: &[u8], limits: &Limits) -> crate::Result<Self> {
+        let kind = TextKind::from_chunk_type(chunk_type).ok_or("not a text chunk")?;
+        let (keyword, rest) = split_null(data)?;
+        let keyword = latin1_decode(keyword);
+
+        let text = match kind {
+            TextKind::Plain => latin1_decode(rest),
+            TextKind::Compressed => match rest.split_first() {
+                Some((0, compressed)) => latin1_decode(&zlib::inflate(compressed, limits)?),
+                _ => return Err("unknown zTXt compression method".into()),
+            },
+            TextKind::International => {
+                let [flag, method, rest @ ..] = rest else {
+                    return Err("iTXt chunk is truncated".into());
+                };
+                let (_language, rest) = split_null(rest)?;
+                let (_translated_keyword, text) = split_null(rest)?;
+
+                match (flag, method) {
+                    (0, _) => String::from_utf8(text.to_vec())?,
+                    (1, 0) => String::from_utf8(zlib::inflate(text, limits)?)?,
+                    _ => return Err("unknown iTXt compression method".into()),
+                }
+            }
+        };
+
+        Ok(Self {
+            kind,
+            keyword,
+            text,
+        })
+    }
+
+    pub fn kind(&self) -> TextKind {
+        self.kind
+    }
+
+    pub fn keyword(&self) -> &str {
+        &self.keyword
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn as_chunk(&self) -> crate::Result<Chunk> {
+        let mut data = latin1_encode(&self.keyword)?;
+        data.push(0);
+
+        match self.kind {
+            TextKind::Plain => data.extend(latin1_encode(&self.text)?),
+            TextKind::Compressed => {
+                data.push(0);
+                data.extend(zlib::deflate(
+                    &latin1_encode(&self.text)?,
+                    zlib::DEFAULT_LEVEL,
+                ));
+            }
+            TextKind::International => {
+                // Uncompressed, with an empty language tag and translated keyword.
+                data.extend([0, 0, 0, 0]);
+                data.extend(self.text.as_bytes());
+            }
+        }
+
+        Ok(Chunk::new(self.kind.chunk_type(), data))
+    }
+}
+
+/// Whether a chunk is a text chunk of any kind stored under `keyword`, without parsing its text.
+pub fn has_keyword(chunk_type: &ChunkType, data: &[u8], keyword: &str) -> bool {
+    TextKind::from_chunk_type(chunk_type).is_some()
+        && split_null(data).is_ok_and(|(k, _)| latin1_encode(keyword).is_ok_and(|kw| k == kw))
+}
+
+/// Finds the first text chunk of any kind stored under `keyword`.
+///
+/// Only chunks with a matching keyword are fully parsed, so unrelated text chunks can't make this
+/// fail.
+pub fn find(png: &PngRef<'_>, keyword: &str, limits: &Limits) -> crate::Result<Option<TextChunk>> {
+    png.chunks()
+        .find(|chunk| has_keyword(chunk.chunk_type(), chunk.data(), keyword))
+        .map(|chunk| TextChunk::parse(chunk.chunk_type(), chunk.data(), limits))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::{tests::PNG_FILE, Png};
+
+    #[test]
+    fn test_text_chunk_round_trip() {
+        let cases = [
+            (TextKind::Plain, "Comment", "Taken in Zürich"),
+            (TextKind::Compressed, "Description", &"dice ".repeat(100)),
+            (TextKind::International, "Title", "Würfel 🎲"),
+        ];
+
+        for (kind, keyword, text) in cases {
+            let chunk = TextChunk::new(kind, keyword, text)
+                .unwrap()
+                .as_chunk()
+                .unwrap();
+            let parsed =
+                TextChunk::parse(chunk.chunk_type(), chunk.data(), &Limits::default()).unwrap();
+            assert_eq!(parsed.kind(), kind);
+            assert_eq!(parsed.keyword(), keyword);
+            assert_eq!(parsed.text(), text);
+        }
+    }
+
+    #[test]
+    fn test_plain_text_bytes() {
+        let chunk = TextChunk::new(TextKind::Plain, "Author", "Zoë")
+            .unwrap()
+            .as_chunk()
+            .unwrap();
+
+        assert_eq!(chunk.chunk_type().to_string(), "tEXt");
+        assert_eq!(chunk.data(), b"Author\0Zo\xEB");
+    }
+
+    #[test]
+    fn test_invalid_keyword() {
+        for keyword in [
+            "",
+            " Comment",
+            "Comment ",
+            "Two  spaces",
+            "Tab\t",
+            &"k".repeat(80),
+        ] {
+            assert!(TextChunk::new(TextKind::Plain, keyword, "text").is_err());
+        }
+
+        assert!(TextChunk::new(TextKind::Plain, "Comment", "🎲").is_err());
+    }
+
+    #[test]
+    fn test_parse_compressed_itxt() {
+        let mut data = b"Title\0\x01\x00en\0Titel\0".to_vec();
+        data.extend(zlib::deflate("compressed".as_bytes(), 6));
+
+        let text = TextChunk::parse(
+            &TextKind::International.chunk_type(),
+            &data,
+            &Limits::default(),
+        );
+        assert_eq!(text.unwrap().text(), "compressed");
+    }
+
+    #[test]
+    fn test_find() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        for (keyword, text) in [("Title", "Dice"), ("Comment", "hidden")] {
+            let chunk = TextChunk::new(TextKind::Compressed, keyword, text).unwrap();
+            png.insert_chunk_before("IEND", chunk.as_chunk().unwrap())
+                .unwrap();
+        }
+
+        let bytes = png.as_bytes();
+        let png = PngRef::try_from(&bytes[..]).unwrap();
+        let found = find(&png, "Comment", &Limits::default()).unwrap().unwrap();
+
+        assert_eq!(found.text(), "hidden");
+        assert!(find(&png, "Author", &Limits::default()).unwrap().is_none());
+    }
+}
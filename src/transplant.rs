@@ -0,0 +1,210 @@
+use crate::{
+    png::{Chunk, ChunkType, Png},
+    text::TextKind,
+};
+use std::fmt::{self, Display, Formatter};
+
+/// Which chunks `transplant` copies. Public critical chunks belong to the image and are never
+/// copied, while private critical chunks are treated like ancillary ones.
+#[derive(Debug, Clone, Default)]
+pub struct TransplantOptions {
+    /// Chunk types to copy, or every ancillary and private type if empty.
+    pub types: Vec<ChunkType>,
+    /// Copy chunks that aren't safe to copy too, even though they may not match the new image.
+    pub allow_unsafe: bool,
+}
+
+/// Why a chunk of the source wasn't copied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The chunk isn't safe to copy, so its meaning depends on the image data it came with.
+    Unsafe,
+    /// The target already has a chunk of this type, and only one is allowed.
+    AlreadyPresent,
+}
+
+impl Display for SkipReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SkipReason::Unsafe => "it isn't safe to copy, since it depends on the image data",
+            SkipReason::AlreadyPresent => "the target already has one",
+        })
+    }
+}
+
+/// What `transplant` did with each chunk of the source it considered.
+#[derive(Debug, Clone, Default)]
+pub struct TransplantReport {
+    pub copied: Vec<Chunk>,
+    /// Chunks that were copied although they aren't safe to copy.
+    pub copied_unsafe: Vec<ChunkType>,
+    pub skipped: Vec<(ChunkType, SkipReason)>,
+}
+
+/// Whether a file may hold more than one chunk of this type. Private chunks are given the benefit
+/// of the doubt, since their rules are unknown.
+fn may_repeat(chunk_type: &ChunkType) -> bool {
+    !chunk_type.is_public()
+        || TextKind::from_chunk_type(chunk_type).is_some()
+        || chunk_type.bytes() == *b"sPLT"
+}
+
+/// Copies the ancillary and private chunks of `source` into `target`.
+///
+/// Each chunk is inserted before the first public critical chunk that followed it in the source and
+/// that the target also has, so chunks that must come before PLTE or IDAT still do. Chunks the
+/// target already has are not copied twice. Unsafe-to-copy chunks are skipped unless
+/// `allow_unsafe` is set, and naming one in `types` without it is an error, as the PNG spec
+/// doesn't let editors carry them over to changed image data.
+pub fn transplant(
+    source: &Png,
+    target: &mut Png,
+    options: &TransplantOptions,
+) -> crate::Result<TransplantReport> {
+    for chunk_type in &options.types {
+        if chunk_type.is_critical() && chunk_type.is_public() {
+            return Err(format!("{chunk_type} is a critical chunk and can't be copied").into());
+        }
+        if !chunk_type.is_safe_to_copy() && !options.allow_unsafe {
+            return Err(format!(
+                "{chunk_type} isn't safe to copy, since its meaning depends on the image data"
+            )
+            .into());
+        }
+    }
+
+    let original: Vec<Chunk> = target.chunks().to_vec();
+    let end_chunk = target.format().end_chunk();
+    let mut report = TransplantReport::default();
+
+    for (i, chunk) in source.chunks().iter().enumerate() {
+        let chunk_type = chunk.chunk_type();
+        if (chunk_type.is_critical() && chunk_type.is_public())
+            || !(options.types.is_empty() || options.types.contains(chunk_type))
+        {
+            continue;
+        }
+
+        if !chunk_type.is_safe_to_copy() && !options.allow_unsafe {
+            report.skipped.push((*chunk_type, SkipReason::Unsafe));
+            continue;
+        }
+
+        let present = original.iter().any(|c| {
+            c.chunk_type() == chunk_type && (c.data() == chunk.data() || !may_repeat(chunk_type))
+        });
+        if present {
+            report
+                .skipped
+                .push((*chunk_type, SkipReason::AlreadyPresent));
+            continue;
+        }
+
+        let anchor = source.chunks()[i + 1..]
+            .iter()
+            .map(Chunk::chunk_type)
+            .filter(|t| t.is_critical() && t.is_public() && t.bytes() != *b"IHDR")
+            .map(ChunkType::to_string)
+            .find(|t| target.chunk_by_type(t).is_some())
+            .unwrap_or_else(|| end_chunk.to_string());
+        target.insert_chunk_before(&anchor, chunk.clone())?;
+
+        if !chunk_type.is_safe_to_copy() {
+            report.copied_unsafe.push(*chunk_type);
+        }
+        report.copied.push(chunk.clone());
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        png::tests::{chunk_types, png_with_text, PNG_FILE},
+        strip::{self, StripOptions},
+    };
+
+    /// The test PNG with every ancillary chunk and its private critical RuSt chunk removed.
+    fn target() -> Png {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let options = StripOptions {
+            remove: vec!["RuSt".parse().unwrap()],
+            ..StripOptions::default()
+        };
+        strip::strip(&mut png, &options).unwrap();
+        png
+    }
+
+    #[test]
+    fn test_transplant() {
+        let mut target = target();
+        let report =
+            transplant(&png_with_text(), &mut target, &TransplantOptions::default()).unwrap();
+
+        assert_eq!(
+            chunk_types(&target),
+            ["IHDR", "pHYs", "IDAT", "RuSt", "tEXt", "IEND"]
+        );
+        assert_eq!(report.copied.len(), 3);
+        assert!(report.copied_unsafe.is_empty());
+        assert_eq!(
+            report.skipped,
+            [
+                ("sRGB".parse().unwrap(), SkipReason::Unsafe),
+                ("gAMA".parse().unwrap(), SkipReason::Unsafe),
+            ]
+        );
+
+        // Copying again adds nothing.
+        let report =
+            transplant(&png_with_text(), &mut target, &TransplantOptions::default()).unwrap();
+        assert!(report.copied.is_empty());
+        assert_eq!(target.chunks().len(), 6);
+    }
+
+    #[test]
+    fn test_unsafe() {
+        let mut target = target();
+        let options = TransplantOptions {
+            types: vec!["sRGB".parse().unwrap()],
+            allow_unsafe: false,
+        };
+        assert!(transplant(&png_with_text(), &mut target, &options).is_err());
+
+        let options = TransplantOptions {
+            allow_unsafe: true,
+            ..options
+        };
+        let report = transplant(&png_with_text(), &mut target, &options).unwrap();
+        assert_eq!(report.copied_unsafe, ["sRGB".parse().unwrap()]);
+        assert_eq!(chunk_types(&target), ["IHDR", "sRGB", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_private_critical() {
+        let mut target = target();
+        let options = TransplantOptions {
+            types: vec!["RuSt".parse().unwrap()],
+            allow_unsafe: false,
+        };
+        let report = transplant(&png_with_text(), &mut target, &options).unwrap();
+
+        assert_eq!(report.copied.len(), 1);
+        assert_eq!(chunk_types(&target), ["IHDR", "IDAT", "RuSt", "IEND"]);
+        assert_eq!(
+            target.chunk_by_type("RuSt").unwrap().data(),
+            png_with_text().chunk_by_type("RuSt").unwrap().data()
+        );
+    }
+
+    #[test]
+    fn test_critical() {
+        let options = TransplantOptions {
+            types: vec!["PLTE".parse().unwrap()],
+            allow_unsafe: true,
+        };
+        assert!(transplant(&png_with_text(), &mut target(), &options).is_err());
+    }
+}
@@ -0,0 +1,129 @@
+use crate::limits::{LimitError, LimitKind, Limits};
+use miniz_oxide::{
+    deflate,
+    inflate::{
+        self,
+        stream::{self, InflateState},
+        TINFLStatus,
+    },
+    DataFormat, MZError, MZFlush, MZStatus,
+};
+
+/// Compression level used when nothing else is asked for, on miniz's scale of 0 to 10.
+pub const DEFAULT_LEVEL: u8 = 6;
+
+/// Compresses `data` into a zlib stream at the given level, from 0 (stored) to 10 (smallest).
+pub fn deflate(data: &[u8], level: u8) -> Vec<u8> {
+    deflate::compress_to_vec_zlib(data, level)
+}
+
+/// Inflates a zlib stream, refusing to produce more than `limits.max_decompressed_size` bytes.
+///
+/// Output is only allocated as it is produced, so a small zlib bomb fails as soon as it crosses
+/// the limit rather than after it has used up memory.
+pub fn inflate(data: &[u8], limits: &Limits) -> crate::Result<Vec<u8>> {
+    match inflate::decompress_to_vec_zlib_with_limit(data, limits.max_decompressed_size) {
+        Ok(output) => Ok(output),
+        Err(err) if err.status == TINFLStatus::HasMoreOutput => Err(LimitError::exceeded(
+            LimitKind::DecompressedSize,
+            limits.max_decompressed_size as u64,
+        )
+        .into()),
+        Err(err) => Err(format!("invalid zlib stream: {err}").into()),
+    }
+}
+
+/// Finds where the zlib stream at the start of `data` ends, including its Adler-32 checksum.
+///
+/// Decoders stop reading there, so anything after it is ignored. The stream is inflated into a
+/// scratch buffer to find its end, and still may not inflate to more than
+/// `limits.max_decompressed_size` bytes.
+pub fn stream_len(data: &[u8], limits: &Limits) -> crate::Result<usize> {
+    let mut state = InflateState::new_boxed(DataFormat::Zlib);
+    let mut scratch = vec![0; 32 * 1024];
+    let mut consumed = 0;
+    let mut written = 0;
+
+    loop {
+        let result = stream::inflate(&mut state, &data[consumed..], &mut scratch, MZFlush::None);
+        consumed += result.bytes_consumed;
+        written += result.bytes_written;
+
+        if written > limits.max_decompressed_size {
+            return Err(LimitError::exceeded(
+                LimitKind::DecompressedSize,
+                limits.max_decompressed_size as u64,
+            )
+            .into());
+        }
+
+        match result.status {
+            Ok(MZStatus::StreamEnd) => return Ok(consumed),
+            Ok(_) if result.bytes_consumed > 0 || result.bytes_written > 0 => {}
+            Ok(_) | Err(MZError::Buf) => return Err("zlib stream is truncated".into()),
+            Err(err) => return Err(format!("invalid zlib stream: {err:?}").into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inflate() {
+        let compressed = deflate(b"This is where your secret message will be!", 6);
+        let inflated = inflate(&compressed, &Limits::default()).unwrap();
+
+        assert_eq!(inflated, b"This is where your secret message will be!");
+    }
+
+    #[test]
+    fn test_inflate_bomb() {
+        let compressed = deflate(&vec![0; 1024 * 1024], 10);
+        let limits = Limits {
+            max_decompressed_size: 64 * 1024,
+            ..Limits::default()
+        };
+
+        let err = inflate(&compressed, &limits).unwrap_err();
+        let err = err.downcast_ref::<LimitError>().unwrap();
+        assert_eq!(err.kind(), LimitKind::DecompressedSize);
+    }
+
+    #[test]
+    fn test_stream_len() {
+        let compressed = deflate(&vec![7; 100_000], 6);
+        let with_slack = [&compressed[..], b"slack"].concat();
+
+        assert_eq!(
+            stream_len(&with_slack, &Limits::default()).unwrap(),
+            compressed.len()
+        );
+        assert_eq!(
+            inflate(&with_slack, &Limits::default()).unwrap(),
+            vec![7; 100_000]
+        );
+
+        assert!(stream_len(&compressed[..compressed.len() - 1], &Limits::default()).is_err());
+        assert!(stream_len(b"not a zlib stream", &Limits::default()).is_err());
+    }
+
+    #[test]
+    fn test_stream_len_bomb() {
+        let compressed = deflate(&vec![0; 1024 * 1024], 10);
+        let limits = Limits {
+            max_decompressed_size: 64 * 1024,
+            ..Limits::default()
+        };
+
+        let err = stream_len(&compressed, &limits).unwrap_err();
+        let err = err.downcast_ref::<LimitError>().unwrap();
+        assert_eq!(err.kind(), LimitKind::DecompressedSize);
+    }
+
+    #[test]
+    fn test_inflate_garbage() {
+        assert!(inflate(b"not a zlib stream", &Limits::default()).is_err());
+    }
+}